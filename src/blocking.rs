@@ -0,0 +1,131 @@
+//! Sending requests without `async`, see [`BlockingClient`].
+
+use std::{io::{self, Write}, net::SocketAddr};
+use crate::{Client, ClientBuilder, DnsConfig, ProxyConfig, RawRequest, ResponseHead, ResponseState, SimpleResponse, Timings};
+
+/// An HTTP client that blocks the current thread until a request is finished.
+///
+/// The `BlockingClient` drives a [`Client`] with its own `mio` event loop, so neither an
+/// executor nor another thread is needed. This is useful for command line tools and build scripts.
+/// It sends one request at a time, use a [`SimpleClient`](crate::SimpleClient) to send many at once.
+///
+/// # Example
+///
+/// ```rust
+/// let mut client = BlockingClient::new()?;
+/// let resp = client.fetch(Request::get().host("example.com"))?;
+/// println!("{}", resp.into_string()?); // note: not all websites use UTF-8!
+/// ```
+///
+/// The timeout of the request is honored, see [`RequestBuilder::timeout`](crate::RequestBuilder::timeout).
+///
+/// # Errors
+/// A request that fails returns the error of [`ResponseState::into_io_error`].
+pub struct BlockingClient {
+    client: Client,
+    io: mio::Poll,
+    events: mio::Events,
+}
+
+impl BlockingClient {
+
+    const CLIENT:  mio::Token = mio::Token(0);
+    const REQUEST: mio::Token = mio::Token(1);
+
+    /// Creates a new client.
+    ///
+    /// An error is a fatal failure and probably means that the system doesn't support all necessary functionality.
+    pub fn new() -> io::Result<Self> {
+        Self::with_client(Client::new(Self::CLIENT))
+    }
+
+    /// Creates a new client that uses a custom dns server.
+    ///
+    /// See [`Client::with_dns_server`].
+    pub fn with_dns_server(server: SocketAddr) -> io::Result<Self> {
+        Self::with_client(Client::with_dns_server(Self::CLIENT, server))
+    }
+
+    /// Creates a new client that uses a custom dns configuration.
+    ///
+    /// See [`Client::with_dns_config`].
+    pub fn with_dns_config(config: DnsConfig) -> io::Result<Self> {
+        Self::with_client(Client::with_dns_config(Self::CLIENT, config))
+    }
+
+    /// Creates a new client that sends all requests through an HTTP proxy.
+    ///
+    /// See [`Client::with_proxy`].
+    pub fn with_proxy(proxy: ProxyConfig) -> io::Result<Self> {
+        Self::with_client(Client::with_proxy(Self::CLIENT, proxy))
+    }
+
+    /// Creates a new client with all options in one place.
+    ///
+    /// The dns token of the builder is ignored, since the client uses its own `mio::Poll`.
+    /// See [`Client::builder`].
+    pub fn with_config(config: ClientBuilder) -> io::Result<Self> {
+        Self::with_client(config.dns_token(Self::CLIENT).build())
+    }
+
+    fn with_client(client: Client) -> io::Result<Self> {
+        Ok(Self { client, io: mio::Poll::new()?, events: mio::Events::with_capacity(32) })
+    }
+
+    /// Send a single request and wait for the whole response.
+    pub fn fetch(&mut self, input: impl TryInto<RawRequest, Error = io::Error>) -> io::Result<SimpleResponse<Vec<u8>>> {
+        let mut body = Vec::with_capacity(2048);
+        let (head, timings) = self.fetch_timed(input, &mut body)?;
+        Ok(SimpleResponse { head, body, timings })
+    }
+
+    /// Send a single request and write the body into `sink` as it arrives.
+    ///
+    /// The body is written whatever the status is, the head is returned once the body is complete.
+    /// If writing fails, the request is cancelled and the error is returned.
+    pub fn fetch_streaming(&mut self, input: impl TryInto<RawRequest, Error = io::Error>, sink: &mut impl Write) -> io::Result<ResponseHead> {
+        self.fetch_timed(input, sink).map(|(head, _timings)| head)
+    }
+
+    /// Sends the request like [`BlockingClient::fetch_streaming`], the timings are queried right after it finished.
+    fn fetch_timed(&mut self, input: impl TryInto<RawRequest, Error = io::Error>, sink: &mut impl Write) -> io::Result<(ResponseHead, Option<Timings>)> {
+
+        // the body is always read here, a redirect is just a head
+        let mut request = input.try_into()?;
+        request.pull_body = false;
+        request.report_redirects = None;
+        let id = self.client.send_raw(&self.io, Self::REQUEST, request)?;
+        let mut head = None;
+
+        loop {
+
+            // wake up in time for the deadline of the request
+            match self.io.poll(&mut self.events, self.client.timeout()) {
+                Ok(()) => (),
+                Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err),
+            }
+
+            for resp in self.client.pump(&self.io, &self.events)? {
+                match resp.state {
+                    ResponseState::Head(value) => head = Some(value),
+                    ResponseState::Data(data) => if let Err(err) = sink.write_all(&data) {
+                        // the connection can't be reused, since the body wasn't read
+                        let _ignored = self.client.cancel(&self.io, id);
+                        return Err(err)
+                    },
+                    ResponseState::Done => {
+                        let head = head.ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no response head"))?;
+                        return Ok((head, self.client.timings(id)))
+                    },
+                    other => if let Some(err) = other.into_io_error() {
+                        return Err(err)
+                    },
+                }
+            }
+
+        }
+
+    }
+
+}
@@ -0,0 +1,151 @@
+//! Decoding response bodies that use chunked transfer encoding.
+
+use std::{io, mem::take};
+use crate::OwnedHeader;
+
+/// How long a chunk size line may be, including chunk extensions.
+const MAX_LINE_LEN: usize = 1024;
+
+/// How large the trailer section may be.
+const MAX_TRAILERS_LEN: usize = 64 * 1024;
+
+/// Reads the chunks from `source` and returns the data they contain.
+///
+/// Running out of data (`WouldBlock`) is not a problem, partially read chunk
+/// headers are kept until more data arrives.
+/// Reading returns `Ok(0)` once the last chunk and the trailers were read.
+pub(crate) struct ChunkedDecoder<R> {
+    source: R,
+    state: DecoderState,
+    line: Vec<u8>, // the partially read chunk size line or trailer section
+    trailers: Vec<OwnedHeader>,
+}
+
+enum DecoderState {
+    Size,
+    Data(usize), // how much of the chunk is left
+    DataEnd,
+    Trailers,
+    Done,
+}
+
+impl<R: io::Read> ChunkedDecoder<R> {
+
+    pub(crate) fn new(source: R) -> Self {
+        Self {
+            source,
+            state: DecoderState::Size,
+            line: Vec::new(),
+            trailers: Vec::new(),
+        }
+    }
+
+    pub(crate) fn get_mut(&mut self) -> &mut R {
+        &mut self.source
+    }
+
+    pub(crate) fn into_inner(self) -> R {
+        self.source
+    }
+
+    /// Returns the trailers, which are only available once the whole body was read.
+    pub(crate) fn take_trailers(&mut self) -> Vec<OwnedHeader> {
+        take(&mut self.trailers)
+    }
+
+    /// Reads into `self.line` until it ends with `end`.
+    fn read_until(&mut self, end: &[u8], max_len: usize) -> io::Result<()> {
+        while !self.line.ends_with(end) {
+            if self.line.len() >= max_len {
+                return Err(invalid("chunk line too long"))
+            }
+            let mut byte = [0];
+            match self.source.read(&mut byte)? {
+                0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                _ => self.line.push(byte[0]),
+            }
+        }
+        Ok(())
+    }
+
+}
+
+impl<R: io::Read> io::Read for ChunkedDecoder<R> {
+
+    fn read(&mut self, buff: &mut [u8]) -> io::Result<usize> {
+
+        loop {
+
+            match self.state {
+
+                DecoderState::Size => {
+                    self.read_until(b"\r\n", MAX_LINE_LEN)?;
+                    let line = take(&mut self.line);
+                    // chunk extensions are ignored, the size always ends at the first `;`
+                    let size = line[..line.len() - 2].split(|byte| *byte == b';').next().unwrap_or_default();
+                    let size = std::str::from_utf8(size).ok()
+                        .and_then(|size| usize::from_str_radix(size.trim(), 16).ok())
+                        .ok_or_else(|| invalid("invalid chunk size"))?;
+                    self.state = if size == 0 { DecoderState::Trailers } else { DecoderState::Data(size) };
+                },
+
+                DecoderState::Data(remaining) => {
+                    if buff.is_empty() { return Ok(0) }
+                    let max = buff.len().min(remaining);
+                    let num = self.source.read(&mut buff[..max])?;
+                    if num == 0 {
+                        return Err(io::ErrorKind::UnexpectedEof.into())
+                    }
+                    self.state = if num == remaining { DecoderState::DataEnd } else { DecoderState::Data(remaining - num) };
+                    return Ok(num)
+                },
+
+                DecoderState::DataEnd => {
+                    while self.line.len() < 2 {
+                        let mut byte = [0];
+                        match self.source.read(&mut byte)? {
+                            0 => return Err(io::ErrorKind::UnexpectedEof.into()),
+                            _ => self.line.push(byte[0]),
+                        }
+                    }
+                    if take(&mut self.line) != b"\r\n" {
+                        return Err(invalid("missing chunk end"))
+                    }
+                    self.state = DecoderState::Size;
+                },
+
+                DecoderState::Trailers => {
+                    // the trailer section is empty or ends with an empty line,
+                    // some servers close the connection without sending the final empty line
+                    match self.read_until(b"\r\n", MAX_TRAILERS_LEN) {
+                        Err(err) if err.kind() == io::ErrorKind::UnexpectedEof && self.line.is_empty() => {
+                            self.state = DecoderState::Done;
+                            continue
+                        },
+                        other => other?,
+                    }
+                    if self.line != b"\r\n" { self.read_until(b"\r\n\r\n", MAX_TRAILERS_LEN)? }
+                    let mut headers = [httparse::EMPTY_HEADER; 64];
+                    match httparse::parse_headers(&self.line, &mut headers) {
+                        Ok(httparse::Status::Complete((_len, headers))) => {
+                            self.trailers = headers.iter().map(OwnedHeader::from).collect();
+                        },
+                        _partial_or_error => return Err(invalid("invalid trailers")),
+                    }
+                    self.line = Vec::new();
+                    self.state = DecoderState::Done;
+                },
+
+                DecoderState::Done => return Ok(0),
+
+            }
+
+        }
+
+    }
+
+}
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
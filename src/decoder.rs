@@ -0,0 +1,83 @@
+
+//! Decoders for compressed response bodies.
+
+use std::io;
+
+#[cfg(any(feature = "compression", feature = "brotli"))]
+use std::{io::Write, mem::take};
+
+/// Decodes a response body according to it's `Content-Encoding`.
+///
+/// The decoders are push based, the raw body data is written into them as it arrives.
+/// This way running out of data (`WouldBlock`) is never a problem.
+pub(crate) enum ContentDecoder {
+    #[cfg(feature = "compression")]
+    Gzip(Box<flate2::write::GzDecoder<Vec<u8>>>),
+    #[cfg(feature = "compression")]
+    Deflate(Box<flate2::write::ZlibDecoder<Vec<u8>>>),
+    #[cfg(feature = "brotli")]
+    Brotli(Box<brotli_decompressor::DecompressorWriter<Vec<u8>>>),
+}
+
+impl ContentDecoder {
+
+    /// Returns a decoder for the `Content-Encoding`, if it is supported.
+    ///
+    /// Returns `None` for `identity` and for unsupported encodings, which
+    /// are passed through unchanged.
+    pub(crate) fn new(encoding: &str) -> Option<Self> {
+        match encoding.trim().to_ascii_lowercase().as_str() {
+            #[cfg(feature = "compression")]
+            "gzip" | "x-gzip" => Some(Self::Gzip(Box::new(flate2::write::GzDecoder::new(Vec::new())))),
+            #[cfg(feature = "compression")]
+            "deflate" => Some(Self::Deflate(Box::new(flate2::write::ZlibDecoder::new(Vec::new())))),
+            #[cfg(feature = "brotli")]
+            "br" => Some(Self::Brotli(Box::new(brotli_decompressor::DecompressorWriter::new(Vec::new(), 4096)))),
+            _identity_or_unsupported => None,
+        }
+    }
+
+    /// Decodes some data, returning everything that could be decoded so far.
+    #[cfg_attr(not(any(feature = "compression", feature = "brotli")), allow(unused_variables))]
+    pub(crate) fn decode(&mut self, data: &[u8]) -> io::Result<Vec<u8>> {
+        match *self {
+            #[cfg(feature = "compression")]
+            Self::Gzip(ref mut decoder) => { decoder.write_all(data)?; Ok(take(decoder.get_mut())) },
+            #[cfg(feature = "compression")]
+            Self::Deflate(ref mut decoder) => { decoder.write_all(data)?; Ok(take(decoder.get_mut())) },
+            #[cfg(feature = "brotli")]
+            Self::Brotli(ref mut decoder) => { decoder.write_all(data)?; Ok(take(decoder.get_mut())) },
+        }
+    }
+
+    /// Finishes decoding, returning the remaining data.
+    ///
+    /// This fails if the compressed stream was incomplete.
+    pub(crate) fn finish(&mut self) -> io::Result<Vec<u8>> {
+        match *self {
+            #[cfg(feature = "compression")]
+            Self::Gzip(ref mut decoder) => { decoder.try_finish()?; Ok(take(decoder.get_mut())) },
+            #[cfg(feature = "compression")]
+            Self::Deflate(ref mut decoder) => { decoder.try_finish()?; Ok(take(decoder.get_mut())) },
+            #[cfg(feature = "brotli")]
+            Self::Brotli(ref mut decoder) => { decoder.close()?; Ok(take(decoder.get_mut())) },
+        }
+    }
+
+}
+
+/// The value of the `Accept-Encoding` header if the user didn't set it.
+#[cfg(all(feature = "compression", feature = "brotli"))]
+pub(crate) const ACCEPT_ENCODING: &str = "gzip, deflate, br";
+
+/// The value of the `Accept-Encoding` header if the user didn't set it.
+#[cfg(all(feature = "compression", not(feature = "brotli")))]
+pub(crate) const ACCEPT_ENCODING: &str = "gzip, deflate";
+
+/// The value of the `Accept-Encoding` header if the user didn't set it.
+#[cfg(all(not(feature = "compression"), feature = "brotli"))]
+pub(crate) const ACCEPT_ENCODING: &str = "br";
+
+/// The value of the `Accept-Encoding` header if the user didn't set it.
+#[cfg(not(any(feature = "compression", feature = "brotli")))]
+pub(crate) const ACCEPT_ENCODING: &str = "identity";
@@ -1,65 +1,268 @@
 
-use mio::net::UdpSocket;
-use std::{io, net::{SocketAddr, Ipv4Addr}, fmt, time::{self, Duration, Instant}};
-use crate::util::{make_socket_addr, register_all, wouldblock, reregister_all};
+use mio::net::{UdpSocket, TcpStream};
+use std::{io::{self, Read, Write}, net::{SocketAddr, IpAddr, Ipv4Addr, Ipv6Addr}, fmt, time::{self, Duration, Instant}};
+use crate::util::{random, connect_tcp, make_socket_addr, register_all, wouldblock, notconnected};
 
-const ME:  SocketAddr = make_socket_addr(Ipv4Addr::new(0, 0, 0, 0), 0);
-const DNS: SocketAddr = make_socket_addr(Ipv4Addr::new(8, 8, 8, 8), 53); // google dns server
-// todo: make custom dns server possible
+const ME:    SocketAddr = make_socket_addr(IpAddr::V4(Ipv4Addr::UNSPECIFIED), 0);
+const ME_V6: SocketAddr = make_socket_addr(IpAddr::V6(Ipv6Addr::UNSPECIFIED), 0);
+const DNS:   SocketAddr = make_socket_addr(IpAddr::V4(Ipv4Addr::new(8, 8, 8, 8)), 53); // google dns server
 // todo: clean this up and loom over the code again
-// todo: remove the dependency on "dns_parser"
+
+/// Configuration for dns resolution.
+///
+/// By default google's dns server (8.8.8.8) is used.
+/// Use [`DnsConfig::from_system`] to use the servers configured in `/etc/resolv.conf`.
+///
+/// # Example
+///
+/// ```rust
+/// # use std::net::SocketAddr;
+/// # use rtv::DnsConfig;
+/// let config = DnsConfig { nameservers: vec![SocketAddr::from(([1, 1, 1, 1], 53))], ..Default::default() };
+/// let mut client = rtv::Client::with_dns_config(mio::Token(0), config);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DnsConfig {
+    /// The servers to ask, in order. If a server doesn't answer in time, the next one is asked.
+    ///
+    /// Only servers of the same ip version as the first one are used.
+    pub nameservers: Vec<SocketAddr>,
+    /// Domains that are appended to hosts with less than `ndots` dots, before trying the host itself.
+    pub search: Vec<String>,
+    pub ndots: usize,
+    /// How long to wait for an answer before asking again.
+    ///
+    /// This doubles every time all servers were asked once.
+    pub timeout: Duration,
+    /// How often every server is asked before giving up.
+    pub attempts: usize,
+    /// Both ipv4 and ipv6 addresses are looked up. If a host has both, this decides which one is used.
+    pub prefer_ipv6: bool,
+    /// The local address queries are sent from, eg. to use a specific network interface.
+    ///
+    /// It has to be of the same ip version as the servers.
+    pub local_address: Option<IpAddr>,
+}
+
+impl Default for DnsConfig {
+    fn default() -> Self {
+        Self {
+            nameservers: vec![DNS],
+            search: Vec::new(),
+            ndots: 1,
+            timeout: Duration::from_secs(1),
+            attempts: 3,
+            prefer_ipv6: false,
+            local_address: None,
+        }
+    }
+}
+
+impl DnsConfig {
+
+    /// Reads the system configuration from `/etc/resolv.conf`.
+    ///
+    /// Falls back to the default configuration if the file can't be read
+    /// or on non-unix platforms.
+    pub fn from_system() -> Self {
+        #[cfg(unix)]
+        if let Ok(contents) = std::fs::read_to_string("/etc/resolv.conf") {
+            return Self::parse(&contents)
+        }
+        Self::default()
+    }
+
+    /// Parses the contents of a `resolv.conf` file.
+    ///
+    /// The `nameserver`, `search`, `domain` and `options ndots:n timeout:n attempts:n inet6` entries are
+    /// supported, everything else is ignored. Defaults are used for missing values.
+    pub fn parse(contents: &str) -> Self {
+
+        let mut config = Self { nameservers: Vec::new(), ..Self::default() };
+
+        for line in contents.lines() {
+            let line = line.split(['#', ';']).next().unwrap_or_default();
+            let mut words = line.split_whitespace();
+            match words.next() {
+                Some("nameserver") => {
+                    // ipv6 addresses may contain a zone, which we can't use
+                    let ip = words.next().and_then(|word| word.split('%').next()?.parse::<IpAddr>().ok());
+                    config.nameservers.extend(ip.map(|ip| SocketAddr::new(ip, 53)));
+                },
+                // the last `search` or `domain` entry wins
+                Some("search") | Some("domain") => {
+                    config.search = words.map(|word| word.trim_end_matches('.').to_string()).collect();
+                },
+                Some("options") => for option in words {
+                    match option.split_once(':') {
+                        Some(("ndots", value)) => config.ndots = value.parse().unwrap_or(config.ndots),
+                        Some(("timeout", value)) => config.timeout = value.parse().map(Duration::from_secs).unwrap_or(config.timeout),
+                        Some(("attempts", value)) => config.attempts = value.parse().unwrap_or(config.attempts).max(1),
+                        None if option == "inet6" => config.prefer_ipv6 = true,
+                        _other => (),
+                    }
+                },
+                _other => (),
+            }
+        }
+
+        if config.nameservers.is_empty() {
+            config.nameservers.push(DNS);
+        }
+
+        config
+
+    }
+
+    /// Returns how long to wait for an answer after sending the query for the `tries`th time.
+    fn retransmit_interval(&self, tries: usize) -> Duration {
+        let round = tries.saturating_sub(1) / self.nameservers.len();
+        self.timeout.saturating_mul(1 << round.min(16))
+    }
+
+    /// Returns the names that should be looked up for `host`, in order.
+    fn names(&self, host: &str) -> Vec<String> {
+        // a trailing dot means the name is absolute
+        if let Some(absolute) = host.strip_suffix('.') {
+            return vec![absolute.to_string()].into_iter().filter(|name| valid_name(name)).collect()
+        }
+        let searched = self.search.iter().map(|domain| format!("{}.{}", host, domain));
+        let names: Vec<String> = if host.matches('.').count() >= self.ndots {
+            Some(host.to_string()).into_iter().chain(searched).collect()
+        } else {
+            searched.chain(Some(host.to_string())).collect()
+        };
+        names.into_iter().filter(|name| valid_name(name)).collect()
+    }
+
+}
 
 pub(crate) struct DnsClient {
     pub(crate) token: mio::Token,
-    socket: Option<UdpSocket>,
-    write_outdated: bool,
+    config: DnsConfig,
+    socket: Option<UdpSocket>, // opened by the first lookup and kept until `shutdown`
     requests: Vec<InternalRequest>,
+    tcp_queries: Vec<TcpQuery>,
     next_id: u16,
 }
 
 impl DnsClient {
 
-    pub(crate) fn new(token: mio::Token) -> Self {
+    pub(crate) fn new(token: mio::Token, mut config: DnsConfig) -> Self {
+        if config.nameservers.is_empty() {
+            config.nameservers.push(DNS);
+        }
+        let family = config.nameservers[0].is_ipv6();
+        config.nameservers.retain(|server| server.is_ipv6() == family);
         Self {
+            config,
             socket: None,
-            write_outdated: false,
             token,
             requests: Vec::new(),
-            next_id: 0
+            tcp_queries: Vec::new(),
+            // answers are only accepted for ids we are waiting for, so they shouldn't be predictable
+            next_id: random() as u16,
         }
     }
 
     pub(crate) fn resolve(&mut self, io: &mio::Poll, host: &str, timeout: Option<Duration>) -> io::Result<DnsId> {
 
         if self.socket.is_none() {
-            let mut socket = UdpSocket::bind(ME)?;
-            socket.connect(DNS)?;
+            // the socket isn't connected, since we might have to talk to multiple servers
+            let local = match self.config.local_address {
+                Some(local_address) => SocketAddr::new(local_address, 0),
+                None => if self.config.nameservers[0].is_ipv6() { ME_V6 } else { ME },
+            };
+            let mut socket = UdpSocket::bind(local)?;
             register_all(io, &mut socket, self.token)?;
             self.socket = Some(socket);
         }
-        
-        if self.write_outdated {
-            let socket = self.socket.as_mut().expect("no socket");
-            reregister_all(io, socket, self.token)?;
-            self.write_outdated = false;
+
+        let names = self.config.names(host);
+        if names.is_empty() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("invalid host name: {}", host)))
+        }
+
+        // an id can't be used twice at once, so the answers can't be mixed up
+        let mut id = self.next_id;
+        while self.requests.iter().any(|request| request.id == id) {
+            id = id.wrapping_add(1);
         }
+        self.next_id = id.wrapping_add(1);
 
-        let id = self.next_id;
-        self.next_id = self.next_id.wrapping_add(1);
+        let now = Instant::now();
 
         self.requests.push(InternalRequest {
             id,
             state: InternalRequestState::Pending,
-            packet: new_dns_packet(id, host),
-            time_created: Instant::now(),
+            packets: new_dns_packets(id, &names[0]),
+            answers: [None, None],
+            targets: [names[0].clone(), names[0].clone()],
+            aliases: 0,
+            names,
+            name_idx: 0,
+            tries: 0,
+            time_created: now,
+            time_sent: now,
             timeout,
         });
 
+        // the socket is usually writable, if it isn't we get a `writable` event later
+        self.send_pending();
+
         Ok(DnsId { inner: id })
 
     }
 
-    pub(crate) fn pump(&mut self, io: &mio::Poll, events: &mio::Events) -> io::Result<Vec<DnsResponse>> {
+    /// Abandons a lookup, no response will be generated for it.
+    pub(crate) fn cancel(&mut self, io: &mio::Poll, id: DnsId) -> io::Result<()> {
+
+        self.requests.retain(|request| request.id != id.inner);
+
+        let mut index = 0;
+        while let Some(query) = self.tcp_queries.get_mut(index) {
+            if query.id == id.inner {
+                io.registry().deregister(&mut query.stream)?;
+                self.tcp_queries.swap_remove(index);
+            } else {
+                index += 1;
+            }
+        }
+
+        Ok(())
+
+    }
+
+    /// Abandons all lookups and closes the socket, it is opened again by the next lookup.
+    pub(crate) fn shutdown(&mut self, io: &mio::Poll) -> io::Result<()> {
+
+        self.requests.clear();
+
+        for mut query in self.tcp_queries.drain(..) {
+            io.registry().deregister(&mut query.stream)?;
+        }
+
+        if let Some(mut socket) = self.socket.take() {
+            io.registry().deregister(&mut socket)?;
+        }
+
+        Ok(())
+
+    }
+
+    /// Returns the time left until the next query has to be retransmitted or times out, if any.
+    pub(crate) fn timeout(&self) -> Option<Duration> {
+        let now = Instant::now();
+        let retransmits = self.requests.iter()
+            .filter(|request| request.state == InternalRequestState::Sent)
+            .map(|request| self.config.retransmit_interval(request.tries).checked_sub(now - request.time_sent).unwrap_or(Duration::ZERO));
+        let timeouts = self.requests.iter()
+            .filter_map(|request| request.timeout.map(|timeout| timeout.checked_sub(now - request.time_created).unwrap_or(Duration::ZERO)));
+        retransmits.chain(timeouts).min()
+    }
+
+    /// Drives the lookups, an error only fails the lookup it belongs to.
+    pub(crate) fn pump(&mut self, io: &mio::Poll, events: &mio::Events) -> Vec<DnsResponse> {
 
         let mut responses = Vec::new();
 
@@ -81,83 +284,295 @@ impl DnsClient {
 
             }
 
+            // the query or the answer might have been lost, so ask the next server
+            else if request.state == InternalRequestState::Sent && self.config.retransmit_interval(request.tries) <= request.time_sent.elapsed() {
+
+                if request.tries >= self.config.attempts * self.config.nameservers.len() {
+
+                    let id = request.id;
+
+                    self.requests.swap_remove(index as usize);
+                    index -= 1;
+
+                    responses.push(DnsResponse {
+                        id: DnsId { inner: id },
+                        outcome: DnsOutcome::TimedOut
+                    })
+
+                } else {
+                    request.state = InternalRequestState::Pending;
+                }
+
+            }
+
             index += 1;
 
         }
 
+        // we might not get a `writable` event for the retried requests, so just try to send them
+        self.send_pending();
+
         for event in events {
 
             if self.token == event.token() {
 
-                // we get another `writable` event after reading the
-                // last response, so there may not be a socket even if we get an event
-                if let Some(ref mut socket) = self.socket {
+                // queries that didn't fit into the socket buffer
+                if event.is_writable() {
+                    self.send_pending();
+                }
 
-                    if event.is_writable() {
+                // the tcp connections use the same token
+                let mut packets = self.pump_tcp(&mut responses);
 
-                        self.write_outdated = true;
-                        for req in self.requests.iter_mut() {
+                if let Some(ref mut socket) = self.socket {
 
-                            if req.state == InternalRequestState::Pending {
+                    if event.is_readable() {
 
-                                socket.send(&req.packet)?;
+                        loop {
+
+                            let mut buff = [0; EDNS_PAYLOAD_SIZE as usize];
 
-                                req.state = InternalRequestState::Sent;
-                                self.write_outdated = false;
+                            let (len, from) = match socket.recv_from(&mut buff) {
+                                Err(err) if wouldblock(&err) => break,
+                                // eg. an icmp error for an earlier query, that query is retransmitted anyway
+                                Err(_other) => break,
+                                Ok(result) => result,
+                            };
 
+                            // the socket isn't connected, so anyone could send us something,
+                            // late answers for lookups that already finished are ignored in `handle_packet`
+                            if self.config.nameservers.contains(&from) {
+                                packets.push((buff[..len].to_vec(), from, Transport::Udp));
                             }
 
                         }
 
                     }
 
-                    if event.is_readable() {
+                }
 
-                        loop {
+                for (packet, from, transport) in packets {
+                    self.handle_packet(io, &packet, from, transport, &mut responses);
+                }
 
-                            let mut buff = [0; 1024];
+                // send the requests for the next names in the search list
+                self.send_pending();
+                
+            }
 
-                            match socket.recv(&mut buff) {
-                                Err(err) if wouldblock(&err) => break,
-                                Err(other) => return Err(other),
-                                Ok(..) => (),
-                            };
+        }
 
-                            let resp = DnsResponse::parse_from_packet(&buff);
+        // close the tcp connections of finished requests
+        let mut index = 0;
+        while let Some(query) = self.tcp_queries.get_mut(index) {
+            if query.done || !self.requests.iter().any(|req| req.id == query.id) {
+                // the stream is closed anyway
+                let _ignored = io.registry().deregister(&mut query.stream);
+                self.tcp_queries.swap_remove(index);
+            } else {
+                index += 1;
+            }
+        }
 
-                            // the request might have timeout out and thus be removed earlier
-                            let maybe_idx = self.requests.iter().position(|req| req.id == resp.id.inner);
-                            if let Some(idx) = maybe_idx {
+        responses
 
-                                responses.push(resp);
+    }
 
-                                self.requests.swap_remove(idx);
+    fn handle_packet(&mut self, io: &mio::Poll, packet: &[u8], from: SocketAddr, transport: Transport, responses: &mut Vec<DnsResponse>) {
 
-                                if self.requests.is_empty() {
-                                    io.registry().deregister(socket)?;
-                                    self.socket = None;
-                                    break
-                                }
+        // a malformed packet is treated as if it was lost
+        let Some(message) = Message::parse(packet) else {
+            return
+        };
 
-                            }
+        let (id, query) = (message.id, message.query);
 
-                        }
+        // the request might have timeout out and thus be removed earlier
+        let Some(idx) = self.requests.iter().position(|req| req.id == id) else {
+            return
+        };
 
-                    }
+        // this might be a late answer for a name we already gave up on
+        let request = &mut self.requests[idx];
+        if !message.name.eq_ignore_ascii_case(&request.targets[query]) {
+            return
+        }
+
+        // the question was already answered, this is a duplicate or a forgery
+        if request.answers[query].is_some() {
+            return
+        }
+
+        // the answer didn't fit, ask the same server again over tcp
+        if message.truncated && transport == Transport::Udp {
+            let mut send_buff = (request.packets[query].len() as u16).to_be_bytes().to_vec();
+            send_buff.extend_from_slice(&request.packets[query]);
+            let stream = connect_tcp(from, self.config.local_address)
+                .and_then(|mut stream| register_all(io, &mut stream, self.token).map(|()| stream));
+            match stream {
+                Ok(stream) => self.tcp_queries.push(TcpQuery { id, stream, server: from, send_buff, recv_buff: Vec::new(), done: false }),
+                // the answer can't be received, this only fails this lookup
+                Err(_err) => self.fail(id, responses),
+            }
+            return
+        }
+
+        let answer = match message.answer() {
+            // follow the CNAME chain using another query
+            Answer::Alias { name } if request.aliases < MAX_ALIASES => {
+                request.aliases += 1;
+                request.packets[query] = new_dns_packet(id, &name, QUERY_TYPES[query]);
+                request.targets[query] = name;
+                request.state = InternalRequestState::Pending;
+                return
+            },
+            Answer::Alias { .. } => Answer::ProtocolError,
+            other => other,
+        };
+
+        request.answers[query] = Some(answer);
+        let Some(outcome) = request.outcome(self.config.prefer_ipv6) else {
+            return // wait for the other answer
+        };
+
+        // try the next name from the search list
+        if matches!(outcome, DnsOutcome::Unknown { .. }) && request.name_idx + 1 < request.names.len() {
+            request.name_idx += 1;
+            request.tries = 0;
+            let name = &request.names[request.name_idx];
+            request.packets = new_dns_packets(request.id, name);
+            request.answers = [None, None];
+            request.targets = [name.clone(), name.clone()];
+            request.aliases = 0;
+            request.state = InternalRequestState::Pending;
+            return
+        }
+
+        responses.push(DnsResponse { id: DnsId { inner: id }, outcome });
+        self.requests.swap_remove(idx);
+
+    }
+
+    /// Gives up on a lookup whose answer can't be received.
+    fn fail(&mut self, id: u16, responses: &mut Vec<DnsResponse>) {
+        if let Some(idx) = self.requests.iter().position(|req| req.id == id) {
+            self.requests.swap_remove(idx);
+            responses.push(DnsResponse { id: DnsId { inner: id }, outcome: DnsOutcome::ProtocolError });
+        }
+    }
+
+    /// Drives the tcp queries, returning the answers that arrived completely.
+    ///
+    /// The lookups whose connection failed before that are failed.
+    fn pump_tcp(&mut self, responses: &mut Vec<DnsResponse>) -> Vec<(Vec<u8>, SocketAddr, Transport)> {
+
+        let mut packets = Vec::new();
+        let mut failed = Vec::new();
+
+        for query in self.tcp_queries.iter_mut().filter(|query| !query.done) {
 
+            let mut closed = false;
+
+            // the stream might not be connected yet
+            while !query.send_buff.is_empty() {
+                match query.stream.write(&query.send_buff) {
+                    Ok(len) => { query.send_buff.drain(..len); },
+                    Err(err) if wouldblock(&err) || notconnected(&err) => break,
+                    // eg. the server refused or reset the connection
+                    Err(_other) => { closed = true; break },
                 }
-                
+            }
+
+            while !closed {
+                let mut buff = [0; 1024];
+                match query.stream.read(&mut buff) {
+                    Ok(0) => closed = true,
+                    Ok(len) => query.recv_buff.extend_from_slice(&buff[..len]),
+                    Err(err) if wouldblock(&err) || notconnected(&err) => break,
+                    Err(_other) => closed = true,
+                }
+            }
+
+            // messages are prefixed with their length
+            let answer = match query.recv_buff[..] {
+                [high, low, ref rest @ ..] => rest.get(..u16::from_be_bytes([high, low]) as usize),
+                _ => None,
+            };
+
+            if let Some(answer) = answer {
+                packets.push((answer.to_vec(), query.server, Transport::Tcp));
+                query.done = true;
+            } else if closed {
+                // the connection ended before the whole answer arrived
+                failed.push(query.id);
+                query.done = true;
             }
 
         }
 
-        Ok(responses)
+        for id in failed {
+            self.fail(id, responses);
+        }
+
+        packets
+
+    }
+
+    /// Sends all pending requests, until the socket would block.
+    ///
+    /// A query that can't be sent is treated as lost, so the next server is asked once it is retransmitted.
+    fn send_pending(&mut self) {
+
+        if let Some(ref socket) = self.socket {
+
+            'send: for req in self.requests.iter_mut() {
+
+                if req.state == InternalRequestState::Pending {
+
+                    // only ask again for what is still missing
+                    let server = self.config.nameservers[req.tries % self.config.nameservers.len()];
+                    for (packet, answer) in req.packets.iter().zip(&req.answers) {
+                        if answer.is_none() {
+                            match socket.send_to(packet, server) {
+                                Ok(..) => (),
+                                Err(err) if wouldblock(&err) => break 'send,
+                                // eg. the server is unreachable
+                                Err(_other) => (),
+                            };
+                        }
+                    }
+
+                    req.state = InternalRequestState::Sent;
+                    req.time_sent = Instant::now();
+                    req.tries += 1;
+
+                }
+
+            }
+
+        }
 
     }
 
 }
 
+/// A query that is retried over tcp, because the answer was truncated.
+struct TcpQuery {
+    id: u16,
+    stream: TcpStream,
+    server: SocketAddr,
+    send_buff: Vec<u8>,
+    recv_buff: Vec<u8>,
+    done: bool,
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
+enum Transport {
+    Udp,
+    Tcp,
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
 pub(crate) struct DnsId {
     pub(crate) inner: u16,
 }
@@ -165,8 +580,15 @@ pub(crate) struct DnsId {
 struct InternalRequest {
     id: u16,
     state: InternalRequestState,
-    packet: Vec<u8>,
+    names: Vec<String>, // the names to try in order, see `DnsConfig::search`
+    name_idx: usize,
+    tries: usize, // how often the query was sent, see `DnsConfig::retransmit_interval`
+    packets: [Vec<u8>; 2], // an A and an AAAA query, see `QUERY_TYPES`
+    answers: [Option<Answer>; 2],
+    targets: [String; 2], // the names the queries ask for, these differ from the name when following a CNAME chain
+    aliases: usize,
     time_created: Instant,
+    time_sent: Instant,
     timeout: Option<Duration>,
 }
 
@@ -176,19 +598,109 @@ enum InternalRequestState {
     Sent,
 }
 
-fn new_dns_packet(id: u16, hostname: &str) -> Vec<u8> {
+impl InternalRequest {
+
+    /// Combines the answers to the A and AAAA queries.
+    ///
+    /// Returns `None` if we still have to wait for an answer.
+    fn outcome(&self, prefer_ipv6: bool) -> Option<DnsOutcome> {
+
+        let [Some(v4), Some(v6)] = &self.answers else {
+            return None
+        };
+
+        let mut addrs = Vec::new();
+        let mut ttl = time::Duration::MAX;
+        let mut protocol_error = false;
+        let mut negative_ttl: Option<time::Duration> = None;
+
+        let ordered = if prefer_ipv6 { [v6, v4] } else { [v4, v6] };
+        for answer in ordered {
+            match answer {
+                Answer::Found { addrs: found, ttl: found_ttl } => { addrs.extend(found); ttl = ttl.min(*found_ttl) },
+                Answer::ProtocolError => protocol_error = true,
+                Answer::NoAddress { ttl: Some(found_ttl) } | Answer::Unknown { ttl: Some(found_ttl) } => {
+                    negative_ttl = Some(negative_ttl.map_or(*found_ttl, |ttl| ttl.min(*found_ttl)))
+                },
+                _unknown_or_no_address => (),
+            }
+        }
 
-    let mut packet = dns_parser::Builder::new_query(id, true);
-    packet.add_question(hostname, false, dns_parser::QueryType::A, dns_parser::QueryClass::IN);
+        Some(if !addrs.is_empty() {
+            DnsOutcome::Known { addrs, ttl }
+        } else if protocol_error {
+            DnsOutcome::ProtocolError
+        } else {
+            DnsOutcome::Unknown { ttl: negative_ttl }
+        })
 
-    packet.build().unwrap()
+    }
 
 }
 
+const TYPE_A:     u16 = 1;
+const TYPE_CNAME: u16 = 5;
+const TYPE_SOA:   u16 = 6;
+const TYPE_AAAA:  u16 = 28;
+const TYPE_OPT:   u16 = 41;
+const CLASS_IN:   u16 = 1;
+
+const QUERY_TYPES: [u16; 2] = [TYPE_A, TYPE_AAAA];
+
+/// How many CNAME records we follow before giving up.
+const MAX_ALIASES: usize = 8;
+
+/// The udp payload size we advertise using edns0, so answers have to be truncated less often.
+const EDNS_PAYLOAD_SIZE: u16 = 4096;
+
+/// Returns `true` if the name can be sent in a query.
+fn valid_name(name: &str) -> bool {
+    name.len() <= 253 && name.split('.').all(|label| !label.is_empty() && label.len() <= 63)
+}
+
+fn new_dns_packets(id: u16, hostname: &str) -> [Vec<u8>; 2] {
+    QUERY_TYPES.map(|query_type| new_dns_packet(id, hostname, query_type))
+}
+
+fn new_dns_packet(id: u16, hostname: &str, query_type: u16) -> Vec<u8> {
+
+    let mut packet = Vec::with_capacity(hostname.len() + 29);
+
+    packet.extend_from_slice(&id.to_be_bytes());
+    packet.extend_from_slice(&[1, 0]); // recursion desired
+    packet.extend_from_slice(&[0, 1, 0, 0, 0, 0, 0, 1]); // one question and one additional record
+
+    for label in hostname.split('.') {
+        packet.push(label.len() as u8);
+        packet.extend_from_slice(label.as_bytes());
+    }
+    packet.push(0);
+    packet.extend_from_slice(&query_type.to_be_bytes());
+    packet.extend_from_slice(&CLASS_IN.to_be_bytes());
+
+    // the OPT record
+    packet.push(0); // root name
+    packet.extend_from_slice(&TYPE_OPT.to_be_bytes());
+    packet.extend_from_slice(&EDNS_PAYLOAD_SIZE.to_be_bytes());
+    packet.extend_from_slice(&[0, 0, 0, 0, 0, 0]); // extended rcode and flags, no data
+
+    packet
+
+}
+
+/// The answer to a single query.
+enum Answer {
+    Found { addrs: Vec<IpAddr>, ttl: time::Duration },
+    Alias { name: String }, // the address has to be asked for seperately
+    NoAddress { ttl: Option<time::Duration> }, // the name exists, but has no address of this type
+    Unknown { ttl: Option<time::Duration> }, // the ttl is taken from the SOA record, if there is one
+    ProtocolError,
+}
+
 #[derive(Debug)]
 pub(crate) enum DnsOutcome {
-    Known { addr: Ipv4Addr, ttl: time::Duration },
-    Unknown,
+    Known { addrs: Vec<IpAddr>, ttl: time::Duration }, // the preferred family comes first
+    Unknown { ttl: Option<time::Duration> }, // how long the host is known not to exist
     ProtocolError,
     TimedOut,
 }
@@ -198,47 +710,234 @@ pub(crate) struct DnsResponse {
     pub(crate) outcome: DnsOutcome,
 }
 
-impl DnsResponse {
+/// A parsed dns response.
+struct Message {
+    id: u16,
+    truncated: bool,
+    response_code: u8,
+    name: String, // from the question
+    query: usize, // the index into `QUERY_TYPES`
+    records: Vec<Record>,
+    negative_ttl: Option<u32>, // from the SOA record in the authority section
+}
+
+struct Record {
+    name: String,
+    ttl: u32,
+    data: RecordData,
+}
+
+enum RecordData {
+    Addr(IpAddr),
+    Alias(String),
+    Other,
+}
+
+impl Message {
+
+    /// Parses a response. Returns `None` if it is malformed or doesn't answer one of our queries.
+    fn parse(buff: &[u8]) -> Option<Self> {
+
+        let mut reader = Reader { buff, pos: 0 };
+
+        let id = reader.u16()?;
+        let flags = reader.u16()?;
+        let question_count = reader.u16()?;
+        let answer_count = reader.u16()?;
+        let authority_count = reader.u16()?;
+        let _additional_count = reader.u16()?;
+
+        // it has to be a response to a single question
+        if flags & 0x8000 == 0 || question_count != 1 {
+            return None
+        }
+
+        let name = reader.name()?;
+        let query_type = reader.u16()?;
+        let class = reader.u16()?;
+        let query = QUERY_TYPES.iter().position(|ty| *ty == query_type).filter(|_| class == CLASS_IN)?;
+
+        let mut records = Vec::with_capacity(answer_count as usize);
+        for _ in 0..answer_count {
+            let name = reader.name()?;
+            let record_type = reader.u16()?;
+            let class = reader.u16()?;
+            let ttl = reader.u32()?;
+            let len = reader.u16()? as usize;
+            let end = reader.pos.checked_add(len).filter(|end| *end <= buff.len())?;
+            // records of other classes, like CH or HS, don't contain internet addresses
+            if class != CLASS_IN {
+                reader.pos = end;
+                continue
+            }
+            let data = match (record_type, len) {
+                (TYPE_A, 4) => RecordData::Addr(IpAddr::from(<[u8; 4]>::try_from(&buff[reader.pos..end]).ok()?)),
+                (TYPE_AAAA, 16) => RecordData::Addr(IpAddr::from(<[u8; 16]>::try_from(&buff[reader.pos..end]).ok()?)),
+                (TYPE_CNAME, _) => RecordData::Alias(reader.name()?),
+                _other_type => RecordData::Other,
+            };
+            reader.pos = end;
+            records.push(Record { name, ttl, data });
+        }
+
+        // a negative answer includes the SOA record of the zone, see RFC 2308 section 5,
+        // a malformed authority section is ignored since the answer itself is fine
+        let negative_ttl = Self::read_negative_ttl(&mut reader, authority_count);
+
+        // the additional section isn't needed
+
+        Some(Self {
+            id,
+            truncated: flags & 0x0200 != 0,
+            response_code: (flags & 0x000f) as u8,
+            name,
+            query,
+            records,
+            negative_ttl,
+        })
+
+    }
+
+    /// Reads the authority section and returns how long a negative answer may be cached.
+    fn read_negative_ttl(reader: &mut Reader, authority_count: u16) -> Option<u32> {
+
+        for _ in 0..authority_count {
+            let _name = reader.name()?;
+            let record_type = reader.u16()?;
+            let _class = reader.u16()?;
+            let ttl = reader.u32()?;
+            let len = reader.u16()? as usize;
+            let end = reader.pos.checked_add(len).filter(|end| *end <= reader.buff.len())?;
+            if record_type == TYPE_SOA {
+                // a short record mustn't be read into the one after it, the default is used then
+                let mut data = Reader { buff: &reader.buff[..end], pos: reader.pos };
+                let _primary = data.name()?;
+                let _mailbox = data.name()?;
+                data.pos += 16; // serial, refresh, retry and expire
+                let minimum = data.u32()?;
+                return Some(ttl.min(minimum))
+            }
+            reader.pos = end;
+        }
+
+        None
 
-    fn parse_from_packet(buff: &[u8]) -> Self {
+    }
 
-        let packet = dns_parser::Packet::parse(buff).unwrap();
+    fn answer(&self) -> Answer {
 
-        let outcome = match packet.header.response_code {
-            dns_parser::ResponseCode::NoError => {
-                match parse_answer(&packet) {
-                    Some((addr, ttl)) => DnsOutcome::Known { addr, ttl },
-                    None => DnsOutcome::ProtocolError,
+        match self.response_code {
+            0 => (),
+            3 => return Answer::Unknown { ttl: self.negative_ttl() },
+            _other => return Answer::ProtocolError,
+        }
+
+        // follow the CNAME records, they should be in order but we don't rely on that
+        let mut name = self.name.as_str();
+        let mut ttl = u32::MAX;
+        for _ in 0..=MAX_ALIASES {
+
+            let matching = self.records.iter().filter(|record| record.name.eq_ignore_ascii_case(name));
+
+            let mut alias = None;
+            let mut addrs = Vec::new();
+            let mut addrs_ttl = ttl;
+            for record in matching {
+                match record.data {
+                    RecordData::Addr(addr) if addr.is_ipv6() == (self.query == 1) => {
+                        addrs.push(addr);
+                        addrs_ttl = addrs_ttl.min(record.ttl);
+                    },
+                    RecordData::Alias(ref target) => { alias = Some(target); ttl = ttl.min(record.ttl) },
+                    _ => (),
                 }
-            },
-            dns_parser::ResponseCode::NameError => {
-                DnsOutcome::Unknown
-            },
-            _ => {
-                DnsOutcome::ProtocolError
             }
-        };
 
-        Self { id: DnsId { inner: packet.header.id }, outcome }
+            if !addrs.is_empty() {
+                return Answer::Found { addrs, ttl: time::Duration::from_secs(addrs_ttl as u64) }
+            }
+
+            match alias {
+                Some(target) => name = target,
+                // the chain ends in another name, which has to be asked for seperately
+                None if name != self.name => return Answer::Alias { name: name.to_string() },
+                None => return Answer::NoAddress { ttl: self.negative_ttl() },
+            }
+
+        }
+
+        Answer::ProtocolError
 
     }
 
+    fn negative_ttl(&self) -> Option<time::Duration> {
+        self.negative_ttl.map(|ttl| time::Duration::from_secs(ttl as u64))
+    }
+
+}
+
+struct Reader<'d> {
+    buff: &'d [u8],
+    pos: usize,
 }
 
-fn parse_answer(packet: &dns_parser::Packet) -> Option<(Ipv4Addr, time::Duration)> {
-    for answer in &packet.answers {
-        if let dns_parser::RData::A(result) = answer.data {
-            return Some((result.0, time::Duration::from_secs(answer.ttl as u64)))
+impl<'d> Reader<'d> {
+
+    fn u16(&mut self) -> Option<u16> {
+        let bytes = self.buff.get(self.pos..self.pos + 2)?;
+        self.pos += 2;
+        Some(u16::from_be_bytes([bytes[0], bytes[1]]))
+    }
+
+    fn u32(&mut self) -> Option<u32> {
+        Some((self.u16()? as u32) << 16 | self.u16()? as u32)
+    }
+
+    /// Reads a possibly compressed name.
+    fn name(&mut self) -> Option<String> {
+
+        let mut name = String::new();
+        let mut pos = self.pos;
+        let mut jumps = 0;
+
+        loop {
+
+            let len = *self.buff.get(pos)? as usize;
+
+            match len & 0xc0 {
+                // a pointer to a name somewhere else
+                0xc0 => {
+                    let low = *self.buff.get(pos + 1)? as usize;
+                    if jumps == 0 { self.pos = pos + 2 }
+                    jumps += 1;
+                    // pointers could form a loop
+                    if jumps > 64 { return None }
+                    pos = (len & 0x3f) << 8 | low;
+                },
+                0x00 if len == 0 => {
+                    if jumps == 0 { self.pos = pos + 1 }
+                    return Some(name)
+                },
+                0x00 => {
+                    let label = self.buff.get(pos + 1 .. pos + 1 + len)?;
+                    if !name.is_empty() { name.push('.') }
+                    name.push_str(&String::from_utf8_lossy(label));
+                    pos += 1 + len;
+                },
+                _reserved => return None,
+            }
+
         }
+
     }
-    None
+
 }
 
 impl fmt::Debug for DnsResponse {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self.outcome {
-            DnsOutcome::Known { addr, ttl } => write!(f, "{:?}, ttl: {:?}", addr, ttl),
-            DnsOutcome::Unknown => write!(f, "Unknown"),
+            DnsOutcome::Known { ref addrs, ttl } => write!(f, "{:?}, ttl: {:?}", addrs, ttl),
+            DnsOutcome::Unknown { .. } => write!(f, "Unknown"),
             DnsOutcome::ProtocolError => write!(f, "Dns Protocol Error"),
             DnsOutcome::TimedOut => write!(f, "TimedOut"),
         }
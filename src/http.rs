@@ -3,13 +3,18 @@
 //!
 //! It provides a [`RequestBuilder`] that allows constructing requests
 //! as well as the [`Response`] type used to receive responses using a [`Client`](crate::Client).
-//! The [`SimpleClient`](crate::SimpleClient) uses it's own response types.
+//! The [`SimpleClient`](crate::SimpleClient) and [`BlockingClient`](crate::BlockingClient) return a whole [`SimpleResponse`] instead.
 
-use std::{fmt, time::Duration, ops::Range, io};
+use std::{fmt, string, borrow::Cow, time::{Duration, SystemTime}, ops::Range, io::{self, Read}, net::{IpAddr, Ipv6Addr, SocketAddr}, sync::{Arc, Mutex}};
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use crate::{ConnectionOptions, UpgradedConnection, decoder::{ContentDecoder, ACCEPT_ENCODING}, util::{split_port, random, parse_http_date}, proxy::base64};
 
 /// An HTTP method.
 /// The default method is `GET`.
-#[derive(Clone, Default)]
+///
+/// Other methods, eg. the ones of WebDAV, can be sent using [`Method::Custom`].
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
 pub enum Method {
     #[default]
     Get,
@@ -20,6 +25,33 @@ pub enum Method {
     Head,
     Options,
     Trace,
+    /// Sent as it is, it has to be a valid token. Custom methods are never considered idempotent
+    /// and the response might always have a body. See [`RequestBuilder::method_str`].
+    Custom(String),
+}
+
+impl Method {
+
+    /// Returns the method like it is sent in the request line.
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Get       => "GET",
+            Self::Post      => "POST",
+            Self::Put       => "PUT",
+            Self::Delete    => "DELETE",
+            Self::Patch     => "PATCH",
+            Self::Head      => "HEAD",
+            Self::Options   => "OPTIONS",
+            Self::Trace     => "TRACE",
+            Self::Custom(method) => method,
+        }
+    }
+
+    /// Returns `true` for `GET`, `HEAD`, `PUT`, `DELETE`, `OPTIONS` and `TRACE`, which can be sent again safely.
+    pub fn is_idempotent(&self) -> bool {
+        matches!(self, Self::Get | Self::Head | Self::Put | Self::Delete | Self::Options | Self::Trace)
+    }
+
 }
 
 /// If the connection should use tls or not.
@@ -29,7 +61,7 @@ pub enum Method {
 /// Secure = HTTPS
 /// ```
 ///
-#[derive(Clone, Copy, Default)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
 pub enum Mode {
     #[default]
     Plain,
@@ -37,19 +69,76 @@ pub enum Mode {
     Secure,
 }
 
+impl Mode {
+
+    /// The port that is used if the request doesn't specify one.
+    ///
+    /// This is `80` for HTTP and `443` for HTTPS.
+    pub fn default_port(self) -> u16 {
+        match self {
+            Self::Plain => 80,
+            #[cfg(feature = "tls")]
+            Self::Secure => 443,
+        }
+    }
+
+}
+
+/// The `Connection` header of a request, see [`RequestBuilder::connection`].
+/// The default is `Close`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConnectionDirective {
+    /// The server should close the connection after the response.
+    /// A [`Client`](crate::Client) that pools connections sends `keep-alive` instead.
+    #[default]
+    Close,
+    /// The connection should stay open after the response.
+    /// It is only reused if the [`Client`](crate::Client) pools connections.
+    KeepAlive,
+    /// The connection should switch to another protocol, the `Upgrade` header has to be set as well.
+    /// The client doesn't change this to `keep-alive`.
+    Upgrade,
+}
+
+impl ConnectionDirective {
+
+    fn value(self) -> &'static str {
+        match self {
+            Self::Close     => "close",
+            Self::KeepAlive => "keep-alive",
+            Self::Upgrade   => "Upgrade",
+        }
+    }
+
+}
+
+/// The HTTP version of a request or response.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Version {
+    Http10,
+    #[default]
+    Http11,
+}
+
 /// An HTTP URI.
 /// The path may start with a `/` or it may not.
+/// If there is no port, the default port of the [`Mode`] is used.
 #[derive(Clone, Default)]
 pub struct Uri<'a> {
     pub host: &'a str,
+    pub port: Option<u16>,
     pub path: &'a str,
 }
 
 /// An HTTP query.
+///
+/// The name and value are percent-encoded when the request is formatted, unless they are already.
 #[derive(Clone)]
 pub struct Query<'a> {
     pub name: &'a str,
     pub value: &'a str,
+    /// The pair is already percent-encoded, like the pairs split out of a [path](RequestBuilder::path).
+    pub encoded: bool,
 }
 
 /// An HTTP header.
@@ -59,6 +148,92 @@ pub struct Header<'a> {
     pub value: &'a str,
 }
 
+/// Credentials sent using the `Authorization` header.
+///
+/// See [`RequestBuilder::basic_auth`] and [`RequestBuilder::bearer_auth`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Auth<'a> {
+    /// `Basic` authentication, the username must not contain a `:`.
+    Basic { username: &'a str, password: Option<&'a str> },
+    /// `Bearer` authentication using a token.
+    Bearer(&'a str),
+}
+
+/// When a failed request is sent again, see [`RequestBuilder::retry`].
+///
+/// Only failures that happen before the response head was received are retried,
+/// so no response was delivered yet. Requests with a streamed body are never retried.
+/// The request keeps its [`ReqId`] and no responses are generated for attempts that are retried.
+///
+/// # Example
+///
+/// Retry up to two times, waiting half a second in between.
+///
+/// ```rust
+/// let policy = RetryPolicy { attempts: 3, backoff: Backoff::Fixed(Duration::from_millis(500)), ..Default::default() };
+/// let req = Request::get().host("example.com").retry(policy);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct RetryPolicy {
+    /// How often the request is sent at most, including the first attempt.
+    pub attempts: u32,
+    /// How long to wait before sending the request again.
+    pub backoff: Backoff,
+    /// Decides if a request that failed with the state is sent again.
+    /// The default is [`RetryPolicy::transient`].
+    pub retry_if: fn(Method, &ResponseState) -> bool,
+}
+
+impl RetryPolicy {
+
+    /// Returns `true` for idempotent methods that failed because of a problem that might go away.
+    ///
+    /// These are failed connection attempts, dns errors and timeouts, and connections
+    /// that were closed before the response head was received.
+    /// Only `GET`, `HEAD`, `PUT`, `DELETE`, `OPTIONS` and `TRACE` requests are idempotent, see [`Method::is_idempotent`].
+    pub fn transient(method: Method, state: &ResponseState) -> bool {
+        method.is_idempotent() && matches!(state, ResponseState::TimedOut(..) | ResponseState::Error(Error::Connect(..) | Error::Dns | Error::HeadIncomplete))
+    }
+
+}
+
+/// The default policy tries up to `3` times, with an exponential backoff starting at `100ms`.
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            attempts: 3,
+            backoff: Backoff::Exponential { initial: Duration::from_millis(100), max: Duration::from_secs(2) },
+            retry_if: Self::transient,
+        }
+    }
+}
+
+/// How long to wait before a request is sent again, see [`RetryPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backoff {
+    /// Always wait the same time.
+    Fixed(Duration),
+    /// Double the time after every attempt, up to `max`.
+    /// A random part of up to half the time is subtracted (jitter), so that many clients don't retry all at once.
+    Exponential { initial: Duration, max: Duration },
+}
+
+impl Backoff {
+
+    /// The time to wait before the `retry`th retry, starting at `1`.
+    pub(crate) fn delay(&self, retry: u32) -> Duration {
+        match *self {
+            Self::Fixed(delay) => delay,
+            Self::Exponential { initial, max } => {
+                let delay = initial.saturating_mul(2u32.saturating_pow(retry.saturating_sub(1))).min(max);
+                let jitter = delay.as_nanos() as u64 / 2;
+                delay - Duration::from_nanos(random() % (jitter + 1))
+            },
+        }
+    }
+
+}
+
 /// The ID assigned to a request.
 ///
 /// You can use it to check if a response belongs to a request.
@@ -81,18 +256,208 @@ impl<'a> RequestBuilder<'a> {
 
     /// Sets the `timeout`.
     /// By default requests do not have a timeout.
+    ///
+    /// This covers the whole request, from the dns lookup to the end of the body.
+    /// The other timeouts only limit a single phase of the request and can't extend it.
     #[inline(always)]
     pub fn timeout(mut self, timeout: Duration) -> Self {
         self.request.timeout = Some(timeout);
         self
     }
 
+    /// Sets how long the dns lookup may take.
+    /// If it takes longer, the request fails with [`Timeout::Dns`].
+    #[inline(always)]
+    pub fn dns_timeout(mut self, timeout: Duration) -> Self {
+        self.request.dns_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how long connecting to an address may take, including the tls handshake
+    /// and sending the request head.
+    ///
+    /// If the host has multiple addresses, the next one is tried after the timeout.
+    /// If all of them time out, the request fails with [`Timeout::Connect`].
+    #[inline(always)]
+    pub fn connect_timeout(mut self, timeout: Duration) -> Self {
+        self.request.connect_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how long the request may go without reading or writing anything.
+    ///
+    /// Unlike the [`timeout`](RequestBuilder::timeout), this is reset whenever data is received or sent,
+    /// so it can be used for long downloads. The time it takes to look up the host and connect counts as well.
+    /// If it expires, the request fails with [`Timeout::Idle`].
+    #[inline(always)]
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.request.idle_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets how long to wait for the response head after the request was sent.
+    /// If it takes longer, the request fails with [`Timeout::Read`].
+    #[inline(always)]
+    pub fn read_timeout(mut self, timeout: Duration) -> Self {
+        self.request.read_timeout = Some(timeout);
+        self
+    }
+
+    /// Sets the socket options of the connection, this overrides the options of the client.
+    ///
+    /// See [`Client::set_connection_options`](crate::Client::set_connection_options).
+    /// If the request reuses a pooled connection, the options of that connection are kept.
+    #[inline(always)]
+    pub fn connection_options(mut self, options: ConnectionOptions) -> Self {
+        self.request.connection_options = Some(options);
+        self
+    }
+
+    /// Sends the request again if it fails, see [`RetryPolicy`].
+    /// By default requests are not retried.
+    ///
+    /// Retries happen within the [`timeout`](RequestBuilder::timeout) of the request,
+    /// the request isn't retried if the backoff would exceed it.
+    #[inline(always)]
+    pub fn retry(mut self, policy: RetryPolicy) -> Self {
+        self.request.retry = Some(policy);
+        self
+    }
+
+    /// Sets the maximum size of the response body in bytes.
+    /// By default the body size is not limited.
+    ///
+    /// If the body is larger, the request fails with [`ResponseState::BodyTooLarge`].
+    /// A `Content-Length` above the limit fails the request right after the head.
+    /// The limit applies to the body as it is received, before it is decompressed.
+    #[inline(always)]
+    pub fn max_body_size(mut self, bytes: u64) -> Self {
+        self.request.max_body_size = Some(bytes);
+        self
+    }
+
+    /// Reports how much of the bodies was transferred using [`ResponseState::Progress`].
+    /// By default no progress is reported.
+    ///
+    /// At most one progress event is sent per call to [`Client::pump`](crate::Client::pump),
+    /// for the uploaded body (see [`send_stream`](RequestBuilder::send_stream)) and for the response body.
+    #[inline(always)]
+    pub fn progress(mut self) -> Self {
+        self.request.progress = true;
+        self
+    }
+
+    /// Leaves the response body in the connection until it is read using [`Client::read_body`](crate::Client::read_body).
+    /// By default the body is read by [`Client::pump`](crate::Client::pump) and sent as [`ResponseState::Data`].
+    ///
+    /// Instead of `Data`, [`ResponseState::Readable`] is sent whenever more of the body can be read.
+    /// The body isn't decompressed, so no `Accept-Encoding` header is sent unless you set one yourself.
+    /// This is only supported by the [`Client`](crate::Client), other clients read the body themselves.
+    #[inline(always)]
+    pub fn pull_body(mut self) -> Self {
+        self.request.pull_body = true;
+        self
+    }
+
+    /// Only receives the head of the response, the body is never read.
+    ///
+    /// `Done` follows right after [`ResponseState::Head`] and the connection is closed, so a huge body isn't downloaded.
+    /// This is useful for health checks or link validation, if the servers don't handle `HEAD` requests well.
+    #[inline(always)]
+    pub fn discard_body(mut self) -> Self {
+        self.request.discard_body = true;
+        self
+    }
+
+    /// Sends [`ResponseState::Redirect`] instead of [`ResponseState::Head`] if the server redirects the request.
+    ///
+    /// These are `301`, `302`, `303`, `307` and `308` responses with a `Location` header that points to an
+    /// `http` or `https` url, the target is resolved against the url of the request. The redirect isn't followed.
+    /// By default the body of a redirect is skipped, see [`RedirectBody`].
+    /// This is only supported by the [`Client`](crate::Client), other clients always send the head.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// client.send(&io, mio::Token(1), Request::get().url("http://example.com/old")?.report_redirects(RedirectBody::Skip))?;
+    /// // ...
+    /// if let ResponseState::Redirect(redirect) = resp.state {
+    ///     let url = redirect.target.url();
+    ///     client.send(&io, mio::Token(2), Request::url(&url)?)?;
+    /// }
+    /// ```
+    #[inline(always)]
+    pub fn report_redirects(mut self, body: RedirectBody) -> Self {
+        self.request.report_redirects = Some(body);
+        self
+    }
+
+    /// Sets the `Connection` header, see [`ConnectionDirective`].
+    /// By default `Connection: close` is sent, or `keep-alive` if the client pools connections.
+    ///
+    /// The connection is never reused if the request asks the server to close it.
+    /// Setting a `Connection` header yourself replaces the directive.
+    #[inline(always)]
+    pub fn connection(mut self, directive: ConnectionDirective) -> Self {
+        self.request.connection = directive;
+        self
+    }
+
+    /// Sets the HTTP version of the request line, the default is `HTTP/1.1`.
+    ///
+    /// Some old devices only understand `HTTP/1.0`. Such a request can't have a chunked body,
+    /// so a [`send_stream`](RequestBuilder::send_stream) body needs a length. Its connection is only reused
+    /// if the server explicitly answers with `Connection: keep-alive`.
+    #[inline(always)]
+    pub fn version(mut self, version: Version) -> Self {
+        self.request.version = version;
+        self
+    }
+
+    /// Attaches a number to the request, which is handed back in every [`Response`] for it.
+    ///
+    /// Use it to find your own state for a response without keeping a map next to the client.
+    /// This is only supported by the [`Client`](crate::Client).
+    #[inline(always)]
+    pub fn tag(mut self, tag: u64) -> Self {
+        self.request.tag = Some(tag);
+        self
+    }
+
     #[inline(always)]
     pub fn method(mut self, method: Method) -> Self {
         self.request.method = method;
         self
     }
 
+    /// Sets the method from its name, eg. `PROPFIND`. Names are case-sensitive.
+    ///
+    /// The standard methods are recognized, any other one becomes a [`Method::Custom`].
+    /// Returns an [`InvalidInput`](io::ErrorKind::InvalidInput) error if the name isn't a valid token.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let req = Request::build().method_str("PROPFIND")?.host("example.com").set("Depth", "1");
+    /// ```
+    pub fn method_str(self, method: &str) -> io::Result<Self> {
+        let method = match method {
+            "GET"     => Method::Get,
+            "POST"    => Method::Post,
+            "PUT"     => Method::Put,
+            "DELETE"  => Method::Delete,
+            "PATCH"   => Method::Patch,
+            "HEAD"    => Method::Head,
+            "OPTIONS" => Method::Options,
+            "TRACE"   => Method::Trace,
+            other => {
+                check_method(other)?;
+                Method::Custom(other.to_string())
+            },
+        };
+        Ok(self.method(method))
+    }
+
     /// Sets the `mode` to [`Mode::Secure`].
     #[cfg(feature = "tls")]
     #[inline(always)]
@@ -108,22 +473,115 @@ impl<'a> RequestBuilder<'a> {
         self.secure()
     }
 
+    /// Sets the mode, host, port, path and queries from a url.
+    ///
+    /// Only `http` and `https` urls are supported and urls with user info (`user@host`) are rejected.
+    /// Percent encoded characters are kept as they are and the fragment is ignored.
+    /// Invalid urls return an [`InvalidInput`](io::ErrorKind::InvalidInput) error.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let req = Request::get().url("https://example.com:8443/search?q=rust&page=2")?;
+    /// ```
+    pub fn url(mut self, url: &'a str) -> io::Result<Self> {
+
+        let invalid = |reason: &str| io::Error::new(io::ErrorKind::InvalidInput, format!("invalid url, {}: {}", reason, url));
+
+        if url.chars().any(|chr| chr.is_whitespace() || chr.is_control()) {
+            return Err(invalid("contains whitespace or control characters"))
+        }
+
+        let (scheme, rest) = url.split_once("://").ok_or_else(|| invalid("no scheme"))?;
+        self.request.mode = match scheme.to_ascii_lowercase().as_str() {
+            "http" => Mode::Plain,
+            #[cfg(feature = "tls")]
+            "https" => Mode::Secure,
+            _other => return Err(invalid("unsupported scheme")),
+        };
+
+        let rest = rest.split('#').next().unwrap_or_default();
+        let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+        let (authority, rest) = rest.split_at(authority_end);
+
+        if authority.contains('@') {
+            return Err(invalid("user info is not supported"))
+        }
+
+        let (host, port) = split_port(authority);
+        let port = port.map(|port| port.parse::<u16>().map_err(|_| invalid("invalid port"))).transpose()?;
+
+        if host.is_empty() || host.starts_with('[') != host.ends_with(']') {
+            return Err(invalid("invalid host"))
+        }
+
+        self.request.uri.host = host;
+        self.request.uri.port = port;
+        Ok(self.path(rest))
+
+    }
+
     /// Set the uri.host component of this request.
+    ///
+    /// Ip addresses are connected to directly, without a dns lookup.
+    /// Ipv6 addresses can be used with or without brackets, eg. `::1` or `[::1]`.
     #[inline(always)]
     pub fn host(mut self, host: &'a str) -> Self {
         self.request.uri.host = host;
         self
     }
 
-    /// Set the uri.path component of this request.
+    /// Set the uri.port component of this request.
+    ///
+    /// By default the port depends on the [`Mode`], see [`Mode::default_port`].
     #[inline(always)]
+    pub fn port(mut self, port: u16) -> Self {
+        self.request.uri.port = Some(port);
+        self
+    }
+
+    /// Send this request over a unix domain socket instead of tcp.
+    ///
+    /// No dns lookup is done and the port is ignored, the host is only used for the `Host` header.
+    /// If no host is set, `localhost` is used. Only plain requests can be sent over a unix socket.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let req = Request::get().unix_socket("/var/run/docker.sock").path("/containers/json");
+    /// ```
+    #[cfg(unix)]
+    #[inline(always)]
+    pub fn unix_socket<P: AsRef<Path> + ?Sized>(mut self, path: &'a P) -> Self {
+        self.request.unix_socket = Some(path.as_ref());
+        self
+    }
+
+    /// Set the uri.path component of this request.
+    ///
+    /// A query string in the path is added as query parameters, like using [`query`](RequestBuilder::query),
+    /// but it is expected to be percent-encoded already. A fragment is removed, since it is never sent.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let req = Request::get().host("example.com").path("/search?q=rust#results").query("page", "2");
+    /// ```
     pub fn path(mut self, path: &'a str) -> Self {
+        let path = path.split('#').next().unwrap_or_default();
+        let (path, query) = path.split_once('?').unwrap_or((path, ""));
         self.request.uri.path = path;
+        self.request.queries.extend(query.split('&').filter(|pair| !pair.is_empty()).map(|pair| {
+            let (name, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Query { name, value, encoded: true }
+        }));
         self
     }
 
     /// Add a query parameter to the path.
     ///
+    /// The name and value are percent-encoded like a form, see [`RequestBuilder::form`].
+    ///
     /// # Example
     ///
     /// The uri `example.com?foo=1&bar=2` could be constructed
@@ -131,7 +589,7 @@ impl<'a> RequestBuilder<'a> {
     /// `Request::build().host("example.com").query("foo", "1").query("bar", "2")`
     #[inline(always)]
     pub fn query(mut self, name: &'a str, value: &'a str) -> Self {
-        self.request.queries.push(Query { name, value });
+        self.request.queries.push(Query { name, value, encoded: false });
         self
     }
 
@@ -156,11 +614,127 @@ impl<'a> RequestBuilder<'a> {
         self.set("User-Agent", value)
     }
 
+    /// Insert the `If-None-Match` header, so the server answers `304 Not Modified` if the [`ETag`](ResponseHead::etag) still matches.
+    #[inline(always)]
+    pub fn if_none_match(self, etag: &'a str) -> Self {
+        self.set("If-None-Match", etag)
+    }
+
+    /// Insert the `If-Modified-Since` header, so the server answers `304 Not Modified` if the resource didn't change since then.
+    ///
+    /// The date has to be an HTTP-date, usually the `Last-Modified` header of the cached response.
+    #[inline(always)]
+    pub fn if_modified_since(self, date: &'a str) -> Self {
+        self.set("If-Modified-Since", date)
+    }
+
+    /// Makes the request conditional on the validators of a cached response, see [`ResponseHead::validators`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let validators = cached.head.validators().unwrap_or_default();
+    /// let req = Request::get().host("example.com").path("/feed.xml").validators(&validators);
+    /// ```
+    pub fn validators(self, validators: &'a Validators) -> Self {
+        let request = match &validators.etag {
+            Some(etag) => self.if_none_match(etag),
+            None => self,
+        };
+        match &validators.last_modified {
+            Some(date) => request.if_modified_since(date),
+            None => request,
+        }
+    }
+
+    /// Use `Basic` authentication, the credentials are base64 encoded when the request is formatted.
+    ///
+    /// Non-ascii credentials are encoded as utf-8. A username containing `:` can't be
+    /// sent this way, [`Request::format`] returns an [`InvalidInput`](io::ErrorKind::InvalidInput) error then.
+    /// This replaces the credentials set by an earlier call to `basic_auth` or [`bearer_auth`](RequestBuilder::bearer_auth).
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let req = Request::get().host("example.com").basic_auth("aladdin", Some("opensesame"));
+    /// ```
+    #[inline(always)]
+    pub fn basic_auth(mut self, username: &'a str, password: Option<&'a str>) -> Self {
+        self.request.auth = Some(Auth::Basic { username, password });
+        self
+    }
+
+    /// Use `Bearer` authentication with the specified token.
+    ///
+    /// This replaces the credentials set by an earlier call to [`basic_auth`](RequestBuilder::basic_auth) or `bearer_auth`.
+    #[inline(always)]
+    pub fn bearer_auth(mut self, token: &'a str) -> Self {
+        self.request.auth = Some(Auth::Bearer(token));
+        self
+    }
+
 
     /// Update the request body with the specified data.
     #[inline(always)]
     pub fn send<T: AsRef<[u8]> + ?Sized>(mut self, body: &'a T) -> Self {
-        self.request.body = body.as_ref();
+        self.request.body = Cow::Borrowed(body.as_ref());
+        self
+    }
+
+    /// Send the pairs as an `application/x-www-form-urlencoded` body.
+    ///
+    /// Names and values are percent encoded, spaces are encoded as `+`.
+    /// Only ascii letters, digits and `*-._` are kept as they are, other bytes of the
+    /// utf-8 encoding are written as `%XX`. The encoded body is owned by the request.
+    /// This replaces the body set using [`send`](RequestBuilder::send) and sets the
+    /// `Content-Type` header, unless it was already set.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let req = Request::post().host("example.com").form(&[("name", "rtv"), ("q", "a b&c")]);
+    /// // the body is `name=rtv&q=a+b%26c`
+    /// ```
+    pub fn form(self, pairs: &[(&str, &str)]) -> Self {
+        let mut body = String::new();
+        for (idx, (name, value)) in pairs.iter().enumerate() {
+            if idx != 0 { body.push('&') }
+            form_encode(name, &mut body);
+            body.push('=');
+            form_encode(value, &mut body);
+        }
+        self.send_owned(body.into_bytes(), "application/x-www-form-urlencoded")
+    }
+
+    /// Replaces the body and sets the `Content-Type` header, unless it was already set.
+    pub(crate) fn send_owned(mut self, body: Vec<u8>, content_type: &'static str) -> Self {
+        self.request.body = Cow::Owned(body);
+        if !self.request.headers.iter().any(|header| header.name.eq_ignore_ascii_case("Content-Type")) {
+            self.request.headers.push(Header { name: "Content-Type", value: content_type });
+        }
+        self
+    }
+
+    /// Stream the request body from a reader while the request is sent.
+    ///
+    /// With a known `len` the `Content-Length` header is sent and exactly `len` bytes are read,
+    /// otherwise the body is sent using chunked transfer encoding until the reader returns `0`.
+    /// This replaces the body set using [`send`](RequestBuilder::send).
+    ///
+    /// The reader is used from within [`Client::pump`](crate::Client::pump), so it should not block for long.
+    /// If it returns a [`WouldBlock`](io::ErrorKind::WouldBlock) error, it is read again the next time `pump` is called.
+    /// If reading fails or the reader ends before `len` bytes, the request fails with [`Error::Upload`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let file = std::fs::File::open("upload.bin")?;
+    /// let len = file.metadata()?.len();
+    /// let req = Request::post().host("example.com").send_stream(file, Some(len));
+    /// ```
+    #[inline(always)]
+    pub fn send_stream(mut self, reader: impl Read + Send + 'static, len: Option<u64>) -> Self {
+        self.request.body_stream = Some(BodyStream { reader: Arc::new(Mutex::new(reader)), len });
         self
     }
 
@@ -174,18 +748,68 @@ impl<'a> RequestBuilder<'a> {
 
 }
 
+/// Percent encodes `input` for a form body, see [`RequestBuilder::form`].
+fn form_encode(input: &str, output: &mut String) {
+    for byte in input.bytes() {
+        match byte {
+            b'a'..=b'z' | b'A'..=b'Z' | b'0'..=b'9' | b'*' | b'-' | b'.' | b'_' => output.push(byte as char),
+            b' ' => output.push('+'),
+            other => output.push_str(&format!("%{:02X}", other)),
+        }
+    }
+}
+
+/// Percent-encodes the bytes that can't be part of a request target, the rest is expected to be encoded already.
+fn escape_invalid(input: &str, output: &mut String) {
+    for byte in input.bytes() {
+        match byte {
+            0x21..=0x7e if byte != b'#' => output.push(byte as char),
+            other => output.push_str(&format!("%{:02X}", other)),
+        }
+    }
+}
+
+/// Checks that `value` is a comma separated list of tokens, like `keep-alive, Upgrade`.
+fn valid_tokens(value: &str) -> bool {
+    value.split(',').map(str::trim).all(valid_token)
+}
+
+/// Checks that a custom method can be sent.
+fn check_method(method: &str) -> io::Result<()> {
+    if !valid_token(method) {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "the method has to be a token"))
+    }
+    // the request target of a `CONNECT` request is the authority, it is only sent to proxies by the client itself
+    if method == "CONNECT" {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, "`CONNECT` requests can't be sent directly"))
+    }
+    Ok(())
+}
+
+/// Checks that `token` only contains the characters allowed in a token, see RFC 7230 section 3.2.6.
+fn valid_token(token: &str) -> bool {
+    !token.is_empty() && token.bytes().all(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte))
+}
+
+/// The host and the port, like in the `Host` header. The port is only included if it isn't the default one.
+fn authority(host: &str, port: u16, mode: Mode) -> String {
+    if port == mode.default_port() { host.to_string() } else { format!("{}:{}", host, port) }
+}
+
 /// This just calls [`finish`](RequestBuilder::finish) and then [`format`](Request::format).
-impl<'a> From<RequestBuilder<'a>> for RawRequest {
+impl<'a> TryFrom<RequestBuilder<'a>> for RawRequest {
+    type Error = io::Error;
     #[inline(always)]
-    fn from(builder: RequestBuilder<'a>) -> Self {
+    fn try_from(builder: RequestBuilder<'a>) -> io::Result<Self> {
         builder.finish().format()
     }
 }
 
 /// This just calls [`format`](Request::format).
-impl<'a> From<Request<'a>> for RawRequest {
+impl<'a> TryFrom<Request<'a>> for RawRequest {
+    type Error = io::Error;
     #[inline(always)]
-    fn from(request: Request<'a>) -> Self {
+    fn try_from(request: Request<'a>) -> io::Result<Self> {
         request.format()
     }
 }
@@ -196,11 +820,20 @@ impl<'a> From<Request<'a>> for RawRequest {
 ///
 /// These headers will be set automatically:
 /// - `Content-Length: ...`
-/// - `Connection: close`
-/// - `Accept-Encoding: identity`
+/// - `Connection: close` (`keep-alive` if the [`Client`](crate::Client) pools connections, see [`RequestBuilder::connection`])
+/// - `Accept-Encoding: identity` (the supported encodings with the `compression` or `brotli` feature)
+///
+/// Setting the `Content-Length` header yourself is an error,
+/// [`Request::format`] returns an [`InvalidInput`](io::ErrorKind::InvalidInput) error then.
+/// The same goes for the `Authorization` header if [`auth`](Request::auth) is set.
+/// The `Host` header is generated from the host and port, setting it yourself replaces it.
+/// Setting the `Connection` header yourself replaces the [`ConnectionDirective`], it has to be a list of tokens.
 ///
 /// You can overwrite the `Accept-Encoding` header
 /// if you wanna receive encoded body data.
+/// With the `compression` feature, `gzip` and `deflate` encoded bodies are decoded
+/// automatically, and so are `br` encoded bodies with the `brotli` feature.
+/// Other encodings are passed through unchanged.
 /// You cannot overwrite the other automatic headers.
 ///
 /// # Example
@@ -222,7 +855,7 @@ impl<'a> From<Request<'a>> for RawRequest {
 ///
 /// ```rust
 /// let req = Request {
-///     uri: Uri { host: "example.com", path: "" },
+///     uri: Uri { host: "example.com", path: "", ..Default::default() },
 ///     timeout: Some(Duration::from_secs(2)),
 ///     ..Default::default(),
 /// };
@@ -231,145 +864,982 @@ impl<'a> From<Request<'a>> for RawRequest {
 #[derive(Clone, Default)]
 pub struct Request<'a> {
     pub timeout: Option<Duration>,
+    pub dns_timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    pub read_timeout: Option<Duration>,
+    pub idle_timeout: Option<Duration>,
+    pub connection_options: Option<ConnectionOptions>,
+    pub retry: Option<RetryPolicy>,
+    pub max_body_size: Option<u64>,
+    pub progress: bool,
+    pub pull_body: bool,
+    pub discard_body: bool,
+    pub report_redirects: Option<RedirectBody>,
+    pub connection: ConnectionDirective,
+    pub version: Version,
+    pub tag: Option<u64>,
     pub method: Method,
     pub mode: Mode,
     pub uri: Uri<'a>,
     pub queries: Vec<Query<'a>>,
     pub headers: Vec<Header<'a>>,
-    pub body: &'a [u8],
+    pub auth: Option<Auth<'a>>,
+    pub body: Cow<'a, [u8]>,
+    pub body_stream: Option<BodyStream>,
+    #[cfg(unix)]
+    pub unix_socket: Option<&'a Path>,
+}
+
+impl<'a> Request<'a> {
+
+    /// Build a request. For [`Method::Get`] and [`Method::Post`] there are two
+    /// convencience functions.
+    /// # Example
+    /// Create a request using a builder and set the method to `Delete`.
+    /// ```rust
+    /// let req = Request::build().method(Method::Delete).host("example.com");
+    /// ```
+    /// Oh no we just deleted the exam-
+    pub fn build() -> RequestBuilder<'a> {
+        RequestBuilder::default()
+    }
+
+    /// Build a request with the `GET` method.
+    /// 
+    /// Other methods are available through [`Method`].
+    pub fn get() -> RequestBuilder<'a> {
+        RequestBuilder::default().method(Method::Get)
+    }
+
+    /// Build a request with the `POST` method.
+    ///
+    /// Other methods are available through [`Method`].
+    pub fn post() -> RequestBuilder<'a> {
+        RequestBuilder::default().method(Method::Post)
+    }
+
+    /// Build a `GET` request from a url.
+    ///
+    /// See [`RequestBuilder::url`].
+    pub fn url(url: &'a str) -> io::Result<RequestBuilder<'a>> {
+        Self::get().url(url)
+    }
+
+    /// Formats this request into valid http bytes.
+    ///
+    /// This will copy all referenced data and thus no longer requires any lifetimes.
+    /// Returns an [`InvalidInput`](io::ErrorKind::InvalidInput) error if a header managed by rtv was set
+    /// or the credentials are invalid.
+    pub fn format(&self) -> io::Result<RawRequest> {
+
+        if let Method::Custom(method) = &self.method {
+            check_method(method)?;
+        }
+        let method = self.method.as_str();
+
+        // ipv6 literals have to be enclosed in brackets
+        let host = match self.uri.host.parse::<Ipv6Addr>() {
+            Ok(..) => format!("[{}]", self.uri.host),
+            #[cfg(unix)]
+            Err(..) if self.uri.host.is_empty() && self.unix_socket.is_some() => "localhost".to_string(),
+            Err(..) => self.uri.host.to_string(),
+        };
+        let trimmed_path = self.uri.path.trim_start_matches("/");
+
+        // nothing can end the request line early
+        let mut path_builder = String::new();
+        escape_invalid(trimmed_path, &mut path_builder);
+        for (idx, query) in self.queries.iter().enumerate() {
+            path_builder += if idx == 0 { "?" } else { "&" };
+            let encode = if query.encoded { escape_invalid } else { form_encode };
+            encode(query.name, &mut path_builder);
+            path_builder += "=";
+            encode(query.value, &mut path_builder);
+        }
+
+        let mut headers = String::new();
+        let mut overwrite_encoding = false;
+        let mut overwrite_host = None;
+
+        match self.body_stream {
+            Some(BodyStream { len: None, .. }) if self.version == Version::Http10 => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "a body without a length can't be sent using HTTP/1.0"))
+            },
+            Some(BodyStream { len: None, .. }) => headers += "Transfer-Encoding: chunked",
+            Some(BodyStream { len: Some(len), .. }) => headers += &format!("Content-Length: {}", len),
+            None => headers += &format!("Content-Length: {}", self.body.len()),
+        };
+        headers += "\r\n";
+
+        // a `Connection` header that was set explicitly replaces the directive
+        let mut connections = self.headers.iter().filter(|header| header.name.eq_ignore_ascii_case("Connection"));
+        let explicit = connections.clone().next().is_some();
+        let connection = match (connections.next(), connections.next()) {
+            (Some(..), Some(..)) => return Err(io::Error::new(io::ErrorKind::InvalidInput, "the `Connection` header was set more than once")),
+            (Some(header), None) if !valid_tokens(header.value) => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "the `Connection` header has to be a list of tokens"))
+            },
+            (Some(header), None) => header.value,
+            (None, ..) => self.connection.value(),
+        };
+        // only the default can be changed to `keep-alive` by the client
+        let connection_fixed = explicit || self.connection != ConnectionDirective::Close;
+
+        headers += "Connection: ";
+        headers += connection;
+        headers += "\r\n";
+
+        for Header { name, value } in self.headers.iter() {
+            if name.eq_ignore_ascii_case("Connection") { continue }
+            if name.eq_ignore_ascii_case("Content-Length")
+            || (name.eq_ignore_ascii_case("Authorization") && self.auth.is_some()) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("the `{}` header is managed by rtv", name)))
+            }
+            else if name.eq_ignore_ascii_case("Accept-Encoding") { overwrite_encoding = true }
+            else if name.eq_ignore_ascii_case("Host") {
+                // the `Host` header has to come first, so it isn't added here
+                if overwrite_host.replace(value).is_some() {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, "the `Host` header was set more than once"))
+                }
+                continue
+            }
+            headers += name;
+            headers += ": ";
+            headers += value;
+            headers += "\r\n";
+        }
+
+        match self.auth {
+            Some(Auth::Basic { username, .. }) if username.contains(':') => {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "the username contains a `:`"))
+            },
+            Some(Auth::Basic { username, password }) => {
+                headers += "Authorization: Basic ";
+                headers += &base64(format!("{}:{}", username, password.unwrap_or_default()).as_bytes());
+                headers += "\r\n";
+            },
+            Some(Auth::Bearer(token)) => {
+                headers += "Authorization: Bearer ";
+                headers += token;
+                headers += "\r\n";
+            },
+            None => (),
+        }
+
+        if !overwrite_encoding && !self.pull_body {
+            headers += "Accept-Encoding: ";
+            headers += ACCEPT_ENCODING;
+            headers += "\r\n";
+        }
+
+        // a `Host` header that was set explicitly replaces the generated one
+        let port = self.uri.port.unwrap_or(self.mode.default_port());
+        let host_header = match overwrite_host {
+            Some(value) => value.to_string(),
+            None => authority(&host, port, self.mode),
+        };
+
+        let version = match self.version {
+            Version::Http10 => "HTTP/1.0",
+            Version::Http11 => "HTTP/1.1",
+        };
+
+        let head = format!("{} /{} {}\r\nHost: {}\r\n{}\r\n", method, path_builder, version, host_header, headers);
+        let connection_idx = head.find("\r\nConnection: ").unwrap() + 14;
+        let mut bytes = head.into_bytes();
+
+        // a streamed body is sent later
+        if self.body_stream.is_none() {
+            bytes.extend_from_slice(&self.body);
+        }
+
+        Ok(RawRequest {
+            bytes,
+            version: self.version,
+            tag: self.tag,
+            method: self.method.clone(),
+            mode: self.mode,
+            port,
+            timeout: self.timeout,
+            dns_timeout: self.dns_timeout,
+            connect_timeout: self.connect_timeout,
+            read_timeout: self.read_timeout,
+            idle_timeout: self.idle_timeout,
+            connection_options: self.connection_options,
+            retry: self.retry,
+            max_body_size: self.max_body_size,
+            progress: self.progress,
+            pull_body: self.pull_body,
+            discard_body: self.discard_body,
+            report_redirects: self.report_redirects,
+            body_stream: self.body_stream.clone(),
+            #[cfg(unix)]
+            unix_socket: self.unix_socket.map(Path::to_path_buf),
+            host,
+            connection: connection_idx .. connection_idx + connection.len(),
+            connection_fixed,
+        })
+
+    }
+
+    /// Copies all borrowed data, so the request can be stored and sent later.
+    pub fn to_owned(&self) -> OwnedRequest {
+        OwnedRequest::from(self.clone())
+    }
+
+}
+
+/// A [`Request`] that owns all of its data, so it doesn't borrow anything.
+///
+/// This is useful to build a request in one place and send it later, eg. from a queue.
+/// It can be sent like a `Request`, all functions that send one also take an `OwnedRequest`.
+///
+/// # Example
+///
+/// ```rust
+/// let path = format!("/users/{}", id);
+/// let req = Request::get().host("example.com").path(&path).finish().to_owned();
+/// drop(path);
+/// client.send(&io, mio::Token(1), req)?;
+/// ```
+#[derive(Clone)]
+pub struct OwnedRequest {
+    request: Request<'static>, // everything that doesn't borrow
+    host: String,
+    path: String,
+    queries: Vec<(String, String, bool)>, // `true` if the pair is encoded
+    headers: Vec<(String, String)>,
+    auth: Option<(bool, String, Option<String>)>, // `true` for `Basic` authentication
+    body: Vec<u8>,
+    #[cfg(unix)]
+    unix_socket: Option<PathBuf>,
+}
+
+impl OwnedRequest {
+
+    /// Borrows the data as a [`Request`].
+    pub fn as_request(&self) -> Request<'_> {
+        Request {
+            uri: Uri { host: &self.host, port: self.request.uri.port, path: &self.path },
+            queries: self.queries.iter().map(|(name, value, encoded)| Query { name, value, encoded: *encoded }).collect(),
+            headers: self.headers.iter().map(|(name, value)| Header { name, value }).collect(),
+            auth: self.auth.as_ref().map(|(basic, username, password)| match basic {
+                true => Auth::Basic { username, password: password.as_deref() },
+                false => Auth::Bearer(username),
+            }),
+            body: Cow::Borrowed(&self.body),
+            #[cfg(unix)]
+            unix_socket: self.unix_socket.as_deref(),
+            ..self.request.clone()
+        }
+    }
+
+}
+
+impl From<Request<'_>> for OwnedRequest {
+    fn from(request: Request<'_>) -> Self {
+        Self {
+            host: request.uri.host.to_string(),
+            path: request.uri.path.to_string(),
+            queries: request.queries.iter().map(|query| (query.name.to_string(), query.value.to_string(), query.encoded)).collect(),
+            headers: request.headers.iter().map(|header| (header.name.to_string(), header.value.to_string())).collect(),
+            auth: request.auth.map(|auth| match auth {
+                Auth::Basic { username, password } => (true, username.to_string(), password.map(str::to_string)),
+                Auth::Bearer(token) => (false, token.to_string(), None),
+            }),
+            body: request.body.into_owned(),
+            #[cfg(unix)]
+            unix_socket: request.unix_socket.map(Path::to_path_buf),
+            request: Request {
+                uri: Uri { host: "", port: request.uri.port, path: "" },
+                queries: Vec::new(),
+                headers: Vec::new(),
+                auth: None,
+                body: Cow::Borrowed(&[]),
+                #[cfg(unix)]
+                unix_socket: None,
+                ..request
+            },
+        }
+    }
+}
+
+impl From<RequestBuilder<'_>> for OwnedRequest {
+    fn from(builder: RequestBuilder<'_>) -> Self {
+        Self::from(builder.finish())
+    }
+}
+
+impl TryFrom<&OwnedRequest> for RawRequest {
+    type Error = io::Error;
+    fn try_from(value: &OwnedRequest) -> Result<Self, Self::Error> {
+        value.as_request().format()
+    }
+}
+
+impl TryFrom<OwnedRequest> for RawRequest {
+    type Error = io::Error;
+    fn try_from(value: OwnedRequest) -> Result<Self, Self::Error> {
+        value.as_request().format()
+    }
+}
+
+pub struct RawRequest {
+    pub bytes: Vec<u8>,
+    pub version: Version,
+    pub tag: Option<u64>,
+    pub method: Method,
+    pub mode: Mode,
+    pub port: u16,
+    pub timeout: Option<Duration>,
+    pub dns_timeout: Option<Duration>,
+    pub connect_timeout: Option<Duration>,
+    pub read_timeout: Option<Duration>,
+    pub idle_timeout: Option<Duration>,
+    pub connection_options: Option<ConnectionOptions>,
+    pub retry: Option<RetryPolicy>,
+    pub max_body_size: Option<u64>,
+    pub progress: bool,
+    pub pull_body: bool,
+    pub discard_body: bool,
+    pub report_redirects: Option<RedirectBody>,
+    #[cfg(unix)]
+    pub unix_socket: Option<PathBuf>,
+    pub(crate) body_stream: Option<BodyStream>,
+    host: String, // the host that is connected to, without the port
+    connection: Range<usize>, // where in `bytes` the value of the `Connection` header is
+    connection_fixed: bool, // the `Connection` header isn't the default, so it is never changed to `keep-alive`
+}
+
+impl RawRequest {
+
+    /// The host, without the port.
+    pub fn host(&self) -> &str {
+        &self.host
+    }
+
+    /// The path and query of the request line, this has to be called before [`RawRequest::set_proxy`].
+    pub(crate) fn path(&self) -> &str {
+        let line = self.bytes.split(|byte| *byte == b'\r').next().unwrap_or_default();
+        let target = line.split(|byte| *byte == b' ').nth(1).unwrap_or_default();
+        std::str::from_utf8(target).unwrap_or("/")
+    }
+
+    /// Rewrites the `Connection` header to `keep-alive`, unless it was chosen explicitly.
+    pub(crate) fn set_keep_alive(&mut self) {
+        if self.connection_fixed { return }
+        const KEEP_ALIVE: &[u8] = b"keep-alive";
+        self.bytes.splice(self.connection.clone(), KEEP_ALIVE.iter().copied());
+        self.connection.end = self.connection.start + KEEP_ALIVE.len();
+    }
+
+    /// Rewrites the request target to absolute-form (`GET http://host/path HTTP/1.1`)
+    /// and adds the `Proxy-Authorization` header, so the request can be sent to a proxy.
+    pub(crate) fn set_proxy(&mut self, authorization: Option<&str>) {
+
+        // the proxy needs the real target, even if the `Host` header was overwritten
+        let target = self.bytes.iter().position(|byte| *byte == b' ').unwrap() + 1;
+        let absolute = format!("http://{}", authority(&self.host, self.port, self.mode));
+
+        self.bytes.splice(target..target, absolute.bytes());
+        self.connection = self.connection.start + absolute.len() .. self.connection.end + absolute.len();
+
+        if let Some(authorization) = authorization {
+            let head_end = self.bytes.windows(4).position(|window| window == b"\r\n\r\n").unwrap() + 2;
+            let header = format!("Proxy-Authorization: {}\r\n", authorization);
+            self.bytes.splice(head_end..head_end, header.bytes());
+        }
+
+    }
+
+    /// Returns `true` if the request asks the server to close the connection after the response.
+    pub(crate) fn closes_connection(&self) -> bool {
+        self.bytes[self.connection.clone()].split(|byte| *byte == b',')
+            .any(|token| token.trim_ascii().eq_ignore_ascii_case(b"close"))
+    }
+
+    /// Rewrites the `Connection` header to `Upgrade` and adds the headers of a WebSocket handshake.
+    pub(crate) fn set_upgrade(&mut self, key: &str) {
+        const UPGRADE: &[u8] = b"Upgrade";
+        self.bytes.splice(self.connection.clone(), UPGRADE.iter().copied());
+        self.connection.end = self.connection.start + UPGRADE.len();
+        let head_end = self.bytes.windows(4).position(|window| window == b"\r\n\r\n").unwrap() + 2;
+        let headers = format!("Upgrade: websocket\r\nSec-WebSocket-Version: 13\r\nSec-WebSocket-Key: {}\r\n", key);
+        self.bytes.splice(head_end..head_end, headers.bytes());
+    }
+
+    /// Returns a `CONNECT` request that asks a proxy to open a tunnel to the host of this request.
+    #[cfg(feature = "tls")]
+    pub(crate) fn connect_request(&self, authorization: Option<&str>) -> Vec<u8> {
+        let authority = format!("{}:{}", self.host(), self.port);
+        let mut head = format!("CONNECT {} HTTP/1.1\r\nHost: {}\r\n", authority, authority);
+        if let Some(authorization) = authorization {
+            head += &format!("Proxy-Authorization: {}\r\n", authorization);
+        }
+        head += "\r\n";
+        head.into_bytes()
+    }
+
+}
+
+/// Requests to the same host that are sent back-to-back on a single connection, see [`Client::send_pipeline`](crate::Client::send_pipeline).
+///
+/// Only `GET` and `HEAD` requests without a streamed body can be pipelined.
+///
+/// # Example
+///
+/// ```rust
+/// let mut pipeline = Pipeline::new();
+/// pipeline.push(Request::get().host("example.com").path("/a.txt"))?;
+/// pipeline.push(Request::get().host("example.com").path("/b.txt"))?;
+/// let ids = client.send_pipeline(&io, mio::Token(1), pipeline)?;
+/// ```
+#[derive(Default)]
+pub struct Pipeline {
+    pub(crate) requests: Vec<RawRequest>,
+}
+
+impl Pipeline {
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a request to the end of the pipeline.
+    ///
+    /// Returns an [`InvalidInput`](io::ErrorKind::InvalidInput) error if the request can't be pipelined
+    /// or goes to another host than the requests before it.
+    pub fn push(&mut self, input: impl TryInto<RawRequest, Error = io::Error>) -> io::Result<()> {
+
+        let request = input.try_into()?;
+
+        if !matches!(request.method, Method::Get | Method::Head) || request.body_stream.is_some() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "only GET and HEAD requests without a streamed body can be pipelined"))
+        }
+
+        if let Some(first) = self.requests.first() {
+            #[cfg(unix)]
+            let same_socket = first.unix_socket == request.unix_socket;
+            #[cfg(not(unix))]
+            let same_socket = true;
+            if first.host() != request.host() || first.port != request.port || first.mode != request.mode || !same_socket {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, "all requests of a pipeline have to go to the same host"))
+            }
+        }
+
+        self.requests.push(request);
+        Ok(())
+
+    }
+
+    pub fn len(&self) -> usize {
+        self.requests.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.requests.is_empty()
+    }
+
+}
+
+/// A request body that is read while the request is sent, see [`RequestBuilder::send_stream`].
+///
+/// Clones share the same reader.
+#[derive(Clone)]
+pub struct BodyStream {
+    reader: Arc<Mutex<dyn Read + Send>>,
+    len: Option<u64>, // `None` if the body is sent chunked
+}
+
+impl BodyStream {
+
+    pub(crate) fn read(&self, buff: &mut [u8]) -> io::Result<usize> {
+        self.reader.lock().unwrap().read(buff)
+    }
+
+    pub(crate) fn len(&self) -> Option<u64> {
+        self.len
+    }
+
+}
+
+/// An owned HTTP header. This is used in a response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OwnedHeader {
+    pub name: String,
+    pub value: String,
+}
+
+impl From<&httparse::Header<'_>> for OwnedHeader {
+    fn from(header: &httparse::Header) -> Self {
+        Self { name: header.name.to_string(), value: String::from_utf8_lossy(header.value).to_string() }
+    }
+}
+
+/// A status code and message for a response.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Status {
+    pub code: StatusCode,
+    /// The reason phrase sent by the server, or the canonical one if it didn't send any.
+    pub reason: String,
+}
+
+impl Status {
+
+    /// Uses the canonical reason phrase if the server sent an empty one.
+    pub(crate) fn parsed(code: u16, reason: Option<&str>) -> Self {
+        let code = StatusCode(code);
+        let reason = reason.map(str::trim).filter(|reason| !reason.is_empty())
+            .or(code.canonical_reason())
+            .unwrap_or_default();
+        Self { code, reason: reason.to_string() }
+    }
+
+    /// The status code as a number.
+    pub fn code(&self) -> u16 {
+        self.code.as_u16()
+    }
+
+    /// See [`StatusCode::is_informational`].
+    pub fn is_informational(&self) -> bool {
+        self.code.is_informational()
+    }
+
+    /// See [`StatusCode::is_success`].
+    pub fn is_success(&self) -> bool {
+        self.code.is_success()
+    }
+
+    /// See [`StatusCode::is_redirect`].
+    pub fn is_redirect(&self) -> bool {
+        self.code.is_redirect()
+    }
+
+    /// See [`StatusCode::is_client_error`].
+    pub fn is_client_error(&self) -> bool {
+        self.code.is_client_error()
+    }
+
+    /// See [`StatusCode::is_server_error`].
+    pub fn is_server_error(&self) -> bool {
+        self.code.is_server_error()
+    }
+
+}
+
+/// An HTTP status code like `404`.
+///
+/// It can be compared with numbers, so `head.status.code == 404` works as well as `head.status.code == StatusCode::NOT_FOUND`.
+/// It is displayed with its canonical reason phrase, eg. `404 Not Found`.
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct StatusCode(u16);
+
+impl StatusCode {
+
+    pub const CONTINUE: Self = Self(100);
+    pub const SWITCHING_PROTOCOLS: Self = Self(101);
+    pub const OK: Self = Self(200);
+    pub const CREATED: Self = Self(201);
+    pub const ACCEPTED: Self = Self(202);
+    pub const NO_CONTENT: Self = Self(204);
+    pub const PARTIAL_CONTENT: Self = Self(206);
+    pub const MOVED_PERMANENTLY: Self = Self(301);
+    pub const FOUND: Self = Self(302);
+    pub const SEE_OTHER: Self = Self(303);
+    pub const NOT_MODIFIED: Self = Self(304);
+    pub const TEMPORARY_REDIRECT: Self = Self(307);
+    pub const PERMANENT_REDIRECT: Self = Self(308);
+    pub const BAD_REQUEST: Self = Self(400);
+    pub const UNAUTHORIZED: Self = Self(401);
+    pub const FORBIDDEN: Self = Self(403);
+    pub const NOT_FOUND: Self = Self(404);
+    pub const METHOD_NOT_ALLOWED: Self = Self(405);
+    pub const NOT_ACCEPTABLE: Self = Self(406);
+    pub const PROXY_AUTHENTICATION_REQUIRED: Self = Self(407);
+    pub const REQUEST_TIMEOUT: Self = Self(408);
+    pub const CONFLICT: Self = Self(409);
+    pub const GONE: Self = Self(410);
+    pub const LENGTH_REQUIRED: Self = Self(411);
+    pub const PRECONDITION_FAILED: Self = Self(412);
+    pub const PAYLOAD_TOO_LARGE: Self = Self(413);
+    pub const URI_TOO_LONG: Self = Self(414);
+    pub const UNSUPPORTED_MEDIA_TYPE: Self = Self(415);
+    pub const RANGE_NOT_SATISFIABLE: Self = Self(416);
+    pub const UNPROCESSABLE_ENTITY: Self = Self(422);
+    pub const TOO_MANY_REQUESTS: Self = Self(429);
+    pub const INTERNAL_SERVER_ERROR: Self = Self(500);
+    pub const NOT_IMPLEMENTED: Self = Self(501);
+    pub const BAD_GATEWAY: Self = Self(502);
+    pub const SERVICE_UNAVAILABLE: Self = Self(503);
+    pub const GATEWAY_TIMEOUT: Self = Self(504);
+
+    pub const fn as_u16(self) -> u16 {
+        self.0
+    }
+
+    /// Returns the reason phrase from the HTTP specification, `None` for unknown codes.
+    pub fn canonical_reason(self) -> Option<&'static str> {
+        Some(match self.0 {
+            100 => "Continue",
+            101 => "Switching Protocols",
+            200 => "OK",
+            201 => "Created",
+            202 => "Accepted",
+            203 => "Non-Authoritative Information",
+            204 => "No Content",
+            205 => "Reset Content",
+            206 => "Partial Content",
+            300 => "Multiple Choices",
+            301 => "Moved Permanently",
+            302 => "Found",
+            303 => "See Other",
+            304 => "Not Modified",
+            307 => "Temporary Redirect",
+            308 => "Permanent Redirect",
+            400 => "Bad Request",
+            401 => "Unauthorized",
+            402 => "Payment Required",
+            403 => "Forbidden",
+            404 => "Not Found",
+            405 => "Method Not Allowed",
+            406 => "Not Acceptable",
+            407 => "Proxy Authentication Required",
+            408 => "Request Timeout",
+            409 => "Conflict",
+            410 => "Gone",
+            411 => "Length Required",
+            412 => "Precondition Failed",
+            413 => "Payload Too Large",
+            414 => "URI Too Long",
+            415 => "Unsupported Media Type",
+            416 => "Range Not Satisfiable",
+            417 => "Expectation Failed",
+            421 => "Misdirected Request",
+            422 => "Unprocessable Entity",
+            425 => "Too Early",
+            426 => "Upgrade Required",
+            428 => "Precondition Required",
+            429 => "Too Many Requests",
+            431 => "Request Header Fields Too Large",
+            451 => "Unavailable For Legal Reasons",
+            500 => "Internal Server Error",
+            501 => "Not Implemented",
+            502 => "Bad Gateway",
+            503 => "Service Unavailable",
+            504 => "Gateway Timeout",
+            505 => "HTTP Version Not Supported",
+            511 => "Network Authentication Required",
+            _unknown => return None,
+        })
+    }
+
+    /// Returns `true` for `1xx` codes, the final response follows.
+    pub fn is_informational(self) -> bool {
+        (100..200).contains(&self.0)
+    }
+
+    /// Returns `true` for `2xx` codes.
+    pub fn is_success(self) -> bool {
+        (200..300).contains(&self.0)
+    }
+
+    /// Returns `true` for `3xx` codes, see [`ResponseHead::location`].
+    pub fn is_redirect(self) -> bool {
+        (300..400).contains(&self.0)
+    }
+
+    /// Returns `true` for `4xx` codes.
+    pub fn is_client_error(self) -> bool {
+        (400..500).contains(&self.0)
+    }
+
+    /// Returns `true` for `5xx` codes.
+    pub fn is_server_error(self) -> bool {
+        (500..600).contains(&self.0)
+    }
+
+}
+
+impl From<u16> for StatusCode {
+    fn from(code: u16) -> Self {
+        Self(code)
+    }
 }
 
-impl<'a> Request<'a> {
+impl From<StatusCode> for u16 {
+    fn from(code: StatusCode) -> Self {
+        code.0
+    }
+}
 
-    /// Build a request. For [`Method::Get`] and [`Method::Post`] there are two
-    /// convencience functions.
-    /// # Example
-    /// Create a request using a builder and set the method to `Delete`.
-    /// ```rust
-    /// let req = Request::build().method(Method::Delete).host("example.com");
-    /// ```
-    /// Oh no we just deleted the exam-
-    pub fn build() -> RequestBuilder<'a> {
-        RequestBuilder::default()
+impl PartialEq<u16> for StatusCode {
+    fn eq(&self, other: &u16) -> bool {
+        self.0 == *other
     }
+}
 
-    /// Build a request with the `GET` method.
-    /// 
-    /// Other methods are available through [`Method`].
-    pub fn get() -> RequestBuilder<'a> {
-        RequestBuilder::default().method(Method::Get)
+impl PartialOrd<u16> for StatusCode {
+    fn partial_cmp(&self, other: &u16) -> Option<std::cmp::Ordering> {
+        self.0.partial_cmp(other)
     }
+}
 
-    /// Build a request with the `POST` method.
-    ///
-    /// Other methods are available through [`Method`].
-    pub fn post() -> RequestBuilder<'a> {
-        RequestBuilder::default().method(Method::Post)
+impl fmt::Display for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.canonical_reason() {
+            Some(reason) => write!(f, "{} {}", self.0, reason),
+            None => write!(f, "{}", self.0),
+        }
     }
+}
 
-    /// Formats this request into valid http bytes.
-    ///
-    /// This will copy all referenced data and thus no longer requires any lifetimes.
-    pub fn format(&self) -> RawRequest {
-
-        let method = match self.method {
-            Method::Get     => "GET",
-            Method::Post    => "POST",
-            Method::Put     => "PUT",
-            Method::Delete  => "DELETE",
-            Method::Patch   => "PATCH",
-            Method::Head    => "HEAD",
-            Method::Options => "OPTIONS",
-            Method::Trace   => "TRACE",
-        };
+impl fmt::Debug for StatusCode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
 
-        let host = self.uri.host;
-        let trimmed_path = self.uri.path.trim_start_matches("/");
+/// Where the server points to using the `Location` header, see [`ResponseHead::location`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Location<'a> {
+    /// A url including the scheme, it can be passed to [`Request::url`].
+    Absolute(&'a str),
+    /// A reference relative to the url of the request, usually a path like `/login?next=home`.
+    Relative(&'a str),
+}
 
-        let mut path_builder = trimmed_path.to_string();
-        for (idx, Query { name, value }) in self.queries.iter().enumerate() {
-            path_builder += if idx == 0 { "?" } else { "&" };
-            path_builder += name;
-            path_builder += "=";
-            path_builder += value;
-        }
+/// A redirect that was reported instead of the head, see [`RequestBuilder::report_redirects`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedirectInfo {
+    /// The head of the response, the status is `head.status`.
+    pub head: ResponseHead,
+    /// The value of the `Location` header as it was sent.
+    pub location: String,
+    /// Where the `Location` header points to.
+    pub target: RedirectTarget,
+}
 
-        let mut headers = String::new();
-        let mut overwrite_encoding = false;
+/// Where a redirect points to, resolved against the url of the request, see [`RedirectInfo`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RedirectTarget {
+    pub mode: Mode,
+    /// Ipv6 addresses are enclosed in brackets, like in a url.
+    pub host: String,
+    pub port: u16,
+    /// The path including the query, it always starts with a `/`.
+    pub path: String,
+}
 
-        headers += "Content-Length: ";
-        headers += &self.body.len().to_string();
-        headers += "\r\n";
+impl RedirectTarget {
 
-        headers += "Connection: close";
-        headers += "\r\n";
+    /// Returns the url of the target, it can be passed to [`Request::url`].
+    pub fn url(&self) -> String {
+        let scheme = match self.mode {
+            Mode::Plain => "http",
+            #[cfg(feature = "tls")]
+            Mode::Secure => "https",
+        };
+        format!("{}://{}{}", scheme, authority(&self.host, self.port, self.mode), self.path)
+    }
 
-        for Header { name, value } in self.headers.iter() {
-            if *name == "Connection" || *name == "Content-Length" {
-                panic!("The `{}` header is managed by rtv, for more info see the `Request` documentation", name);
-            }
-            else if *name == "Accept-Encoding" { overwrite_encoding = true }
-            headers += name;
-            headers += ": ";
-            headers += value;
-            headers += "\r\n";
-        }
+    /// Resolves a reference, like the value of a `Location` header, against this target, see RFC 3986 section 5.
+    ///
+    /// Returns `None` if the reference is empty, invalid or has a scheme other than `http` and `https`.
+    pub(crate) fn resolve(&self, reference: &str) -> Option<Self> {
 
-        if overwrite_encoding {
-            headers += "Accept-Encoding: identity";
-            headers += "\r\n";
+        let reference = reference.trim().split('#').next().unwrap_or_default();
+        if reference.is_empty() || reference.chars().any(|chr| chr.is_whitespace() || chr.is_control()) {
+            return None
         }
 
-        let head = format!("{} /{} HTTP/1.1\r\nHost: {}\r\n{}\r\n", method, trimmed_path, host, headers);
-        let host_idx = head.find("Host: ").unwrap() + 6;
-        let mut bytes = head.into_bytes();
+        // a scheme can't contain a `/` or `?`, so a colon in the path or query is never mistaken for one
+        let scheme = reference.split_once(':').filter(|(scheme, _rest)| {
+            scheme.starts_with(|chr: char| chr.is_ascii_alphabetic()) && scheme.chars().all(|chr| chr.is_ascii_alphanumeric() || "+-.".contains(chr))
+        });
+
+        let (mode, network_path) = match scheme {
+            Some((scheme, rest)) => {
+                let mode = match scheme.to_ascii_lowercase().as_str() {
+                    "http" => Mode::Plain,
+                    #[cfg(feature = "tls")]
+                    "https" => Mode::Secure,
+                    _other => return None,
+                };
+                (mode, Some(rest.strip_prefix("//")?))
+            },
+            // protocol-relative, eg. `//example.com/path`
+            None => (self.mode, reference.strip_prefix("//")),
+        };
+
+        let Some(rest) = network_path else {
+            let base_path = self.path.split('?').next().unwrap_or_default();
+            let path = if reference.starts_with('/') {
+                reference.to_string()
+            } else if reference.starts_with('?') {
+                format!("{}{}", base_path, reference)
+            } else {
+                // relative to the directory of the request path
+                let directory = &base_path[..base_path.rfind('/').map_or(0, |slash| slash + 1)];
+                format!("{}{}", directory, reference)
+            };
+            return Some(Self { mode: self.mode, host: self.host.clone(), port: self.port, path: remove_dot_segments(&path) })
+        };
 
-        bytes.extend_from_slice(self.body);
+        let authority_end = rest.find(['/', '?']).unwrap_or(rest.len());
+        let (authority, path) = rest.split_at(authority_end);
+        if authority.contains('@') {
+            return None
+        }
 
-        RawRequest {
-            bytes,
-            mode: self.mode,
-            timeout: self.timeout,
-            host: host_idx .. host_idx + self.uri.host.len()
+        let (host, port) = split_port(authority);
+        let port = match port {
+            Some(port) if !port.is_empty() => port.parse().ok()?,
+            _default => mode.default_port(),
+        };
+        if host.is_empty() || host.starts_with('[') != host.ends_with(']') {
+            return None
         }
 
+        let path = if path.starts_with('/') { path.to_string() } else { format!("/{}", path) };
+        Some(Self { mode, host: host.to_string(), port, path: remove_dot_segments(&path) })
+
     }
 
 }
 
-pub struct RawRequest {
-    pub bytes: Vec<u8>,
-    pub mode: Mode,
-    pub timeout: Option<Duration>,
-    host: Range<usize>, // where in `bytes` the host is
+/// Removes the `.` and `..` segments from a path that starts with a `/`, the query is kept as it is.
+fn remove_dot_segments(path: &str) -> String {
+    let (path, query) = path.split_once('?').map_or((path, None), |(path, query)| (path, Some(query)));
+    let mut segments = Vec::new();
+    for segment in path.split('/').skip(1) {
+        match segment {
+            "." => (),
+            ".." => { segments.pop(); },
+            segment => segments.push(segment),
+        }
+    }
+    // a trailing dot segment refers to a directory
+    if path.ends_with("/.") || path.ends_with("/..") {
+        segments.push("");
+    }
+    let mut resolved = format!("/{}", segments.join("/"));
+    if let Some(query) = query {
+        resolved += "?";
+        resolved += query;
+    }
+    resolved
 }
 
-impl RawRequest {
-    pub fn host(&self) -> &str {
-        std::str::from_utf8(
-            &self.bytes[self.host.clone()]
-        ).unwrap()
-    }
+/// Whether the body of a redirect is delivered, see [`RequestBuilder::report_redirects`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RedirectBody {
+    /// The request is done after [`ResponseState::Redirect`], like with [`RequestBuilder::discard_body`].
+    Skip,
+    /// The body follows as usual.
+    Deliver,
 }
 
-/// An owned HTTP header. This is used in a response.
+/// The headers of a cached response that tell the server which version of a resource you have.
+///
+/// See [`ResponseHead::validators`] and [`RequestBuilder::validators`]. The values are kept as they were sent.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Validators {
+    /// The `ETag` header, sent back as `If-None-Match`.
+    pub etag: Option<String>,
+    /// The `Last-Modified` header, sent back as `If-Modified-Since`.
+    pub last_modified: Option<String>,
+}
+
+/// The parsed `Content-Type` header, see [`ResponseHead::content_type`].
+///
+/// The media type and the parameter names are lowercase, since they are case-insensitive.
+/// Parameter values are kept as they were sent, without the quotes.
 #[derive(Debug, Clone, PartialEq, Eq)]
-pub struct OwnedHeader {
-    pub name: String,
-    pub value: String,
+pub struct ContentType {
+    essence: String, // `type/subtype`
+    params: Vec<(String, String)>,
 }
 
-impl From<&httparse::Header<'_>> for OwnedHeader {
-    fn from(header: &httparse::Header) -> Self {
-        Self { name: header.name.to_string(), value: String::from_utf8_lossy(header.value).to_string() }
+impl ContentType {
+
+    /// Parses the value of a `Content-Type` header like `text/html; charset=utf-8`.
+    ///
+    /// Returns `None` if there is no valid media type, invalid parameters are skipped.
+    pub fn parse(value: &str) -> Option<Self> {
+
+        let (essence, mut rest) = value.split_once(';').unwrap_or((value, ""));
+        let essence = essence.trim().to_ascii_lowercase();
+        let (type_, subtype) = essence.split_once('/')?;
+        if !valid_token(type_) || !valid_token(subtype) {
+            return None
+        }
+
+        let mut params = Vec::new();
+        while !rest.is_empty() {
+            let end = rest.find([';', '=']).unwrap_or(rest.len());
+            let name = rest[..end].trim().to_ascii_lowercase();
+            // a parameter without a value is skipped
+            if !rest[end..].starts_with('=') {
+                rest = rest.get(end + 1..).unwrap_or_default();
+                continue
+            }
+            let value;
+            (value, rest) = match rest[end + 1..].trim_start().strip_prefix('"') {
+                Some(quoted) => unquote(quoted),
+                None => {
+                    let (value, after) = rest[end + 1..].split_once(';').unwrap_or((&rest[end + 1..], ""));
+                    (value.trim().to_string(), after)
+                },
+            };
+            if valid_token(&name) {
+                params.push((name, value));
+            }
+        }
+
+        Some(Self { essence, params })
+
+    }
+
+    /// The media type without parameters, like `text/html`.
+    pub fn essence(&self) -> &str {
+        &self.essence
+    }
+
+    /// The part before the slash, like `text`.
+    pub fn type_(&self) -> &str {
+        self.essence.split_once('/').map_or("", |(type_, _subtype)| type_)
+    }
+
+    /// The part after the slash, like `html`.
+    pub fn subtype(&self) -> &str {
+        self.essence.split_once('/').map_or("", |(_type, subtype)| subtype)
+    }
+
+    /// The `charset` parameter, like `utf-8`.
+    pub fn charset(&self) -> Option<&str> {
+        self.param("charset")
+    }
+
+    /// The value of a parameter, the name is compared case-insensitively.
+    pub fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(param, _value)| param.eq_ignore_ascii_case(name)).map(|(_param, value)| value.as_str())
     }
+
 }
 
-/// A status code and message for a response.
-#[derive(Debug, Clone, PartialEq, Eq)]
-pub struct Status {
-    pub code: u16,
-    pub reason: String,
+/// Reads a quoted string up to the closing quote, returns the value and the rest after the next `;`.
+fn unquote(quoted: &str) -> (String, &str) {
+    let mut value = String::new();
+    let mut chars = quoted.char_indices();
+    while let Some((idx, chr)) = chars.next() {
+        match chr {
+            '\\' => value.extend(chars.next().map(|(_idx, escaped)| escaped)),
+            '"' => return (value, quoted[idx + 1..].split_once(';').map_or("", |(_garbage, after)| after)),
+            other => value.push(other),
+        }
+    }
+    // the closing quote is missing
+    (value, "")
 }
 
 /// The `Head` of a response. This is not to be confused with an HTTP `Header`.
@@ -380,19 +1850,70 @@ pub struct Status {
 /// including all headers and more.
 #[derive(Clone, PartialEq, Eq)]
 pub struct ResponseHead {
+    pub version: Version,
     pub status: Status,
     pub headers: Vec<OwnedHeader>,
-    // `0` if not present
-    pub content_length: usize,
+    // `None` if not present or ignored because of a `Transfer-Encoding`
+    // this is the length of the encoded body, so it is useless if the body was decompressed
+    pub content_length: Option<u64>,
     // `true` if chunked transfer encoding is used
     pub transfer_chunked: bool,
+    // `None` if the connection uses a unix socket
+    pub connection: Option<ConnectionInfo>,
+    // `None` if the connection doesn't use tls
+    #[cfg(feature = "tls")]
+    pub tls: Option<TlsInfo>,
+}
+
+/// The connection a response was received on, see [`ResponseHead::connection`].
+///
+/// If the request was sent through a proxy, the peer is the proxy.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    pub peer_addr: SocketAddr,
+    pub local_addr: SocketAddr,
+    /// `true` if the host wasn't resolved for this request, because its address was cached
+    /// or a pooled connection was reused.
+    /// Always `false` for ip addresses.
+    pub dns_cached: bool,
+}
+
+/// How long each phase of a request took, see [`Client::timings`](crate::Client::timings).
+///
+/// If the request was sent again, eg. because of its [`RetryPolicy`], the phases are the ones of the last attempt.
+/// If the request failed, the phase it failed in lasts until then and the ones it didn't reach take no time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Timings {
+    /// `None` if the host wasn't resolved, because it is an ip address or its address was cached.
+    pub dns: Option<Duration>,
+    /// Zero if a pooled connection was reused. Through a proxy, this includes opening the tunnel.
+    pub connect: Duration,
+    /// `None` if the connection doesn't use tls or no handshake was needed.
+    pub tls_handshake: Option<Duration>,
+    /// Since the request was sent, until the first byte of the response head arrived.
+    pub time_to_first_byte: Duration,
+    pub total: Duration,
+}
+
+/// What was negotiated during the tls handshake, see [`ResponseHead::tls`].
+///
+/// For certificate pinning, compare the fingerprint of the first peer certificate and cancel the request if it doesn't match.
+#[cfg(feature = "tls")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TlsInfo {
+    pub protocol_version: rustls::ProtocolVersion,
+    pub cipher_suite: rustls::CipherSuite,
+    /// The DER encoded certificate chain of the server, starting with its own certificate.
+    pub peer_certificates: Vec<Vec<u8>>,
+    /// The protocol chosen using ALPN, see [`Client::set_alpn_protocols`](crate::Client::set_alpn_protocols).
+    pub alpn: Option<Vec<u8>>,
 }
 
 impl ResponseHead {
 
     /// Get the value of a header. Returns `None` if the header could not be found.
     ///
-    /// This does a linear search through the inner vec.
+    /// The name is compared case-insensitively. This does a linear search through the inner vec.
     pub fn get_header<'d>(&'d self, name: &str) -> Option<&'d str> {
         self.headers.iter().find_map(Self::match_header(name))
     }
@@ -402,8 +1923,142 @@ impl ResponseHead {
         self.headers.iter().filter_map(Self::match_header(name))
     }
 
+    /// Returns the `Location` header, which tells where a redirect or a newly created resource is.
+    ///
+    /// Urls with a scheme are [`Location::Absolute`], everything else has to be resolved
+    /// against the url of the request. The [`Client`](crate::Client) can do this for redirects,
+    /// see [`RequestBuilder::report_redirects`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// if head.status.is_redirect() {
+    ///     if let Some(Location::Absolute(url)) = head.location() {
+    ///         client.send(&io, mio::Token(2), Request::url(url)?)?;
+    ///     }
+    /// }
+    /// ```
+    pub fn location(&self) -> Option<Location<'_>> {
+        let location = self.get_header("Location")?.trim();
+        let scheme = location.split_once("://").map(|(scheme, _rest)| scheme);
+        let absolute = scheme.is_some_and(|scheme| !scheme.is_empty() && scheme.chars().all(|chr| chr.is_ascii_alphanumeric() || "+-.".contains(chr)));
+        Some(if absolute { Location::Absolute(location) } else { Location::Relative(location) })
+    }
+
+    /// Returns the `ETag` header, including the quotes and the `W/` prefix of a weak tag.
+    ///
+    /// It is sent back as it is, see [`RequestBuilder::if_none_match`].
+    pub fn etag(&self) -> Option<&str> {
+        self.get_header("ETag").map(str::trim).filter(|etag| !etag.is_empty())
+    }
+
+    /// Returns the parsed `Last-Modified` header, `None` if there is none or it isn't a valid HTTP-date.
+    ///
+    /// All three formats of RFC 9110 are understood, dates before 1970 aren't.
+    pub fn last_modified(&self) -> Option<SystemTime> {
+        parse_http_date(self.get_header("Last-Modified")?)
+    }
+
+    /// Returns the `ETag` and `Last-Modified` headers, which are needed to revalidate the response later.
+    ///
+    /// Returns `None` if the response has neither. See [`RequestBuilder::validators`].
+    pub fn validators(&self) -> Option<Validators> {
+        let validators = Validators {
+            etag: self.etag().map(str::to_string),
+            last_modified: self.get_header("Last-Modified").map(str::trim).filter(|date| !date.is_empty()).map(str::to_string),
+        };
+        (validators != Validators::default()).then_some(validators)
+    }
+
+    /// Returns the parsed `Content-Type` header, `None` if there is none or it is invalid.
+    ///
+    /// For `text/html; charset=utf-8` the [essence](ContentType::essence) is `text/html`.
+    pub fn content_type(&self) -> Option<ContentType> {
+        ContentType::parse(self.get_header("Content-Type")?)
+    }
+
+    /// Returns `true` if the `Content-Type` is `application/json` or ends with `+json`, like `application/ld+json`.
+    pub fn is_json(&self) -> bool {
+        self.content_type().is_some_and(|content_type| content_type.essence() == "application/json" || content_type.subtype().ends_with("+json"))
+    }
+
+    /// Returns `true` if the `Content-Type` is a `text/*` type, json or xml.
+    pub fn is_text(&self) -> bool {
+        self.content_type().is_some_and(|content_type| {
+            content_type.type_() == "text" || content_type.essence() == "application/xml" || content_type.subtype().ends_with("+xml")
+        }) || self.is_json()
+    }
+
+    /// The length of the body as it is delivered, `None` if it isn't known in advance.
+    ///
+    /// The `Content-Length` is only used if the body isn't chunked or decompressed.
+    pub(crate) fn body_length(&self, method: &Method) -> Option<u64> {
+        if *method == Method::Head || matches!(self.status.code(), 100..=199 | 204 | 304) { return Some(0) }
+        let decoded = self.get_header("Content-Encoding").and_then(ContentDecoder::new).is_some();
+        if self.transfer_chunked || decoded { None } else { self.content_length }
+    }
+
+    /// Returns `true` if the server wants to close the connection after this response.
+    ///
+    /// All `Connection` headers are considered and their tokens are compared case-insensitively.
+    /// Without an explicit `close` or `keep-alive` token, `HTTP/1.0` defaults to closing
+    /// and `HTTP/1.1` defaults to keeping the connection alive.
+    pub fn connection_close(&self) -> bool {
+        let mut close = false;
+        let mut keep_alive = false;
+        for token in self.header_tokens("Connection") {
+            if token.eq_ignore_ascii_case("close") { close = true }
+            else if token.eq_ignore_ascii_case("keep-alive") { keep_alive = true }
+        }
+        if close { true }
+        else if keep_alive { false }
+        else { self.version == Version::Http10 }
+    }
+
+    /// Get the parameters of the `Keep-Alive` header. Returns `None` if the header could not be found.
+    ///
+    /// Unknown or invalid parameters are ignored.
+    pub fn keep_alive(&self) -> Option<KeepAlive> {
+        let mut found = false;
+        let mut keep_alive = KeepAlive::default();
+        for header in self.headers.iter().filter(|header| header.name.eq_ignore_ascii_case("Keep-Alive")) {
+            found = true;
+            for param in header.value.split(',') {
+                let Some((name, value)) = param.split_once('=') else { continue };
+                let (name, value) = (name.trim(), value.trim().trim_matches('"'));
+                if name.eq_ignore_ascii_case("timeout") {
+                    keep_alive.timeout = value.parse().ok().map(Duration::from_secs);
+                } else if name.eq_ignore_ascii_case("max") {
+                    keep_alive.max = value.parse().ok();
+                }
+            }
+        }
+        found.then_some(keep_alive)
+    }
+
+    /// Returns `true` if the server explicitly wants to keep the connection alive after this response.
+    pub(crate) fn keeps_alive(&self) -> bool {
+        !self.connection_close() && self.header_tokens("Connection").any(|token| token.eq_ignore_ascii_case("keep-alive"))
+    }
+
+    /// Returns the keep-alive parameters if the connection may be reused after this response.
+    pub(crate) fn reuse(&self) -> Option<KeepAlive> {
+        if self.connection_close() { return None }
+        let keep_alive = self.keep_alive().unwrap_or_default();
+        if keep_alive.max == Some(0) { None } else { Some(keep_alive) }
+    }
+
+    /// Iterate over the comma separated tokens of all headers named `name`, ignoring case.
+    fn header_tokens<'d>(&'d self, name: &'d str) -> impl Iterator<Item = &'d str> {
+        self.headers.iter()
+            .filter(move |header| header.name.eq_ignore_ascii_case(name))
+            .flat_map(|header| header.value.split(','))
+            .map(str::trim)
+            .filter(|token| !token.is_empty())
+    }
+
     fn match_header<'d>(name: &'d str) -> impl for<'e> Fn(&'e OwnedHeader) -> Option<&'e str> + 'd { // i know the `+ 'd` is technically incorrect
-        move |header| if header.name == name { Some(&header.value[..]) } else { None }
+        move |header| if header.name.eq_ignore_ascii_case(name) { Some(&header.value[..]) } else { None }
     }
 
 }
@@ -417,36 +2072,129 @@ impl fmt::Debug for ResponseHead {
                 writeln!(f, "        {}: {}", header.name, header.value)?;
             }
             writeln!(f, "    ]")?;
+            writeln!(f, "    version: {:?}", self.version)?;
             writeln!(f, "    status: {:?}", self.status)?;
             writeln!(f, "    content_length: {:?}", self.content_length)?;
             writeln!(f, "    transfer_chunked: {:?}", self.transfer_chunked)?;
+            if let Some(connection) = &self.connection {
+                writeln!(f, "    connection: {} -> {}, dns cached: {}", connection.local_addr, connection.peer_addr, connection.dns_cached)?;
+            }
+            #[cfg(feature = "tls")]
+            if let Some(tls) = &self.tls {
+                writeln!(f, "    tls: {:?}, {:?}, {} peer certificates", tls.protocol_version, tls.cipher_suite, tls.peer_certificates.len())?;
+                if let Some(protocol) = &tls.alpn {
+                    writeln!(f, "    alpn: {}", String::from_utf8_lossy(protocol))?;
+                }
+            }
             write!(f, "}}")?;
             Ok(())
         } else {
             if self.transfer_chunked {
                 write!(f, "ResponseHead {{ status: {}: {}, transfer_chunked: true, ... }}",
-                    self.status.code,
+                    self.status.code(),
                     self.status.reason)
-            } else {
+            } else if let Some(content_length) = self.content_length {
                 write!(f, "ResponseHead {{ status: {}: {}, content_length: {}, ... }}",
-                    self.status.code,
+                    self.status.code(),
                     self.status.reason,
-                    self.content_length)
+                    content_length)
+            } else {
+                write!(f, "ResponseHead {{ status: {}: {}, ... }}",
+                    self.status.code(),
+                    self.status.reason)
             }
         }
     }
 }
 
+/// The parameters of a `Keep-Alive` response header.
+///
+/// See [`ResponseHead::keep_alive`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct KeepAlive {
+    /// How long the server will keep an idle connection open.
+    pub timeout: Option<Duration>,
+    /// How many more requests the server will accept on this connection.
+    pub max: Option<usize>,
+}
+
+/// A simple response.
+///
+/// Use the alternate debug formatter `{:#?}` to print out verbose information
+/// including all headers and more.
+///
+/// The `body` may be a [`Vec<u8>`](std::vec::Vec), or a [`BodyReader`](crate::BodyReader).
+/// It is returned by the [`SimpleClient`](crate::SimpleClient) and the [`BlockingClient`](crate::BlockingClient).
+#[derive(Clone)]
+pub struct SimpleResponse<B> {
+    pub head: ResponseHead,
+    pub body: B,
+    // `None` until the whole body was received, eg. if it is read using a `BodyReader`
+    pub timings: Option<Timings>,
+}
+
+impl SimpleResponse<Vec<u8>> {
+
+    /// Convert the request body into a `String`.
+    /// Note that the data is assumed to be valid utf8, see [`SimpleResponse::text`]
+    /// for other text encodings.
+    pub fn into_string(self) -> Result<String, string::FromUtf8Error> {
+        String::from_utf8(self.body)
+    }
+
+    /// Convert the body into a `String`, invalid utf8 is replaced with `U+FFFD`.
+    pub fn into_string_lossy(self) -> String {
+        match String::from_utf8(self.body) {
+            Ok(text) => text,
+            Err(err) => String::from_utf8_lossy(err.as_bytes()).into_owned(),
+        }
+    }
+
+    /// Decode the body using the `charset` of the `Content-Type`, like `ISO-8859-1` or `Shift_JIS`.
+    ///
+    /// Utf8 is used if there is no charset or it is unknown, a byte order mark overrides the charset.
+    /// Invalid bytes are replaced with `U+FFFD`.
+    #[cfg(feature = "encoding")]
+    pub fn text(&self) -> String {
+        let charset = self.head.content_type().and_then(|content_type| content_type.charset().map(str::to_string));
+        let encoding = charset.and_then(|charset| encoding_rs::Encoding::for_label(charset.as_bytes())).unwrap_or(encoding_rs::UTF_8);
+        let (text, _encoding, _malformed) = encoding.decode(&self.body);
+        text.into_owned()
+    }
+
+    /// Deserialize the json body.
+    ///
+    /// Fails with [`JsonError::ContentType`](crate::JsonError::ContentType) if the `Content-Type` is not
+    /// `application/json` (or another `+json` type) and with [`JsonError::Deserialize`](crate::JsonError::Deserialize)
+    /// if the body is invalid. The status code is not checked.
+    #[cfg(feature = "json")]
+    pub fn json<T: serde::de::DeserializeOwned>(&self) -> Result<T, crate::JsonError> {
+        crate::json::from_body(&self.head, &self.body)
+    }
+
+}
+
+impl<B> fmt::Debug for SimpleResponse<B> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if f.alternate() {
+            write!(f, "SimpleResponse {{ head: {:?}, ... }}", self.head)
+        } else {
+            write!(f, "SimpleResponse {{ status: {}, {}, ... }}", self.head.status.code(), self.head.status.reason)
+        }
+    }
+}
+
 /// An HTTP response. Contains a [`ResponseState`].
 ///
 /// A `Response` is **not** a full HTTP response but just one part of it. This arcitecture
 /// allows for streaming the response data, not waiting for everything to arrive.
-/// It also contains the response id, that could be obtained earlier when sending the request.
+/// It also contains the response id, that could be obtained earlier when sending the request,
+/// and the tag of the request if it has one, see [`RequestBuilder::tag`].
 /// # Example
 /// Here is an example of how matching against a response might look.
 /// ```rust
 /// match resp.state {
-///     ResponseState::Head(head) => println!("content_length is {} bytes", head.content_length),
+///     ResponseState::Head(head) => println!("content_length is {:?} bytes", head.content_length),
 ///     ResponseState::Data(some_data) => response_data_buffer.extend_from_slice(&some_data),
 ///     other if other.is_error() => panic!("error: {:?}", other),
 ///     ...
@@ -456,12 +2204,13 @@ impl fmt::Debug for ResponseHead {
 pub struct Response {
     pub id: ReqId,
     pub state: ResponseState,
+    pub tag: Option<u64>,
 }
 
 impl Response {
 
     pub(crate) fn new(id_num: usize, state: ResponseState) -> Self {
-        Self { id: ReqId { inner: id_num }, state }
+        Self { id: ReqId { inner: id_num }, state, tag: None }
     }
 
 }
@@ -478,23 +2227,53 @@ impl Response {
 ///
 /// After eiteher of these three methods have returned `true` you will receive no more
 /// events for this Request.
-#[derive(PartialEq, Eq)]
+#[derive(PartialEq)]
 pub enum ResponseState {
     /// The response head. Contains information about what the response contains.
     Head(ResponseHead),
+    /// The server redirected the request, this is sent instead of the head, see [`RequestBuilder::report_redirects`].
+    Redirect(RedirectInfo),
     /// We have read **some** data for this request. The data is not transmitted all at once,
     /// everytime the server sends a chunk of data you will receive one of these.
+    /// Bytes the server sends after the announced `Content-Length` are never part of the body.
     Data(Vec<u8>),
+    /// The trailer fields sent after the last chunk of a chunked body.
+    /// This is only sent if there are any trailers, right before [`ResponseState::Done`].
+    Trailers(Vec<OwnedHeader>),
+    /// How much of the bodies was transferred so far, see [`RequestBuilder::progress`].
+    /// This is sent at most once per call to [`Client::pump`](crate::Client::pump).
+    Progress(Progress),
+    /// More of the body can be read using [`Client::read_body`](crate::Client::read_body), see [`RequestBuilder::pull_body`].
+    /// This is sent right after the head as well. Read until it returns a [`WouldBlock`](std::io::ErrorKind::WouldBlock)
+    /// error, since this is only sent again once new data arrives.
+    Readable,
+    /// Connecting to one of the host's addresses failed, the next one is tried.
+    Fallback,
     /// The request is done and will not generate any more events.
     Done,
-    /// The request timed out. This will only occur if you set a timeout for a request.
-    TimedOut,
-    /// The server unexpectedly closed the connection for this request.
-    Aborted,
+    /// The request timed out, this contains which timeout expired.
+    TimedOut(Timeout),
+    /// The request failed, this contains the reason.
+    Error(Error),
     /// The host could not be found.
     UnknownHost,
-    /// An http protocol error occured while reading the response. For example the server could've send invalid data.
-    ProtocolError,
+    /// The proxy refused to open a tunnel to the host, this contains the status it responded with.
+    ProxyError(Status),
+    /// The response head had too many headers or was too large.
+    /// See [`Client::set_max_headers`](crate::Client::set_max_headers) and [`Client::set_max_head_size`](crate::Client::set_max_head_size).
+    HeadTooLarge,
+    /// The response body was larger than allowed. See [`RequestBuilder::max_body_size`].
+    BodyTooLarge,
+    /// The server switched to the WebSocket protocol and the connection is yours now, see [`Client::upgrade`](crate::Client::upgrade).
+    /// This is sent right after the head and the request is done.
+    Upgraded(UpgradedConnection),
+    /// The address of the host and how long it is valid, see [`Client::resolve`](crate::Client::resolve).
+    /// The lookup is done.
+    Resolved(IpAddr, Duration),
+    /// The client was shut down before the request finished, see [`Client::shutdown`](crate::Client::shutdown).
+    Cancelled,
+    /// The [`RequestPolicy`] of the client doesn't allow the request, this contains the reason.
+    Blocked(Blocked),
 }
 
 impl ResponseState {
@@ -516,37 +2295,67 @@ impl ResponseState {
     pub fn is_done(&self) -> bool {
         match self {
             Self::Head(..)      => false,
+            Self::Redirect(..)  => false,
             Self::Data(..)      => false,
+            Self::Trailers(..)  => false,
+            Self::Progress(..)  => false,
+            Self::Readable      => false,
+            Self::Fallback      => false,
             Self::Done          => true, // <-
-            Self::TimedOut      => false,
-            Self::Aborted       => false,
+            Self::TimedOut(..)  => false,
+            Self::Error(..)     => false,
             Self::UnknownHost   => false,
-            Self::ProtocolError => false,
+            Self::ProxyError(..) => false,
+            Self::HeadTooLarge  => false,
+            Self::BodyTooLarge  => false,
+            Self::Upgraded(..)  => true, // <-
+            Self::Resolved(..)  => true, // <-
+            Self::Cancelled     => false,
+            Self::Blocked(..)   => false,
         }
     }
 
-    /// Returns `true` if this state is either `Dead`, `TimedOut`, `UnknownHost` or `Error`.
+    /// Returns `true` if this state is either `Error`, `TimedOut`, `UnknownHost` or another error.
     ///
     /// If true, this request will no longer generate any events.
     pub fn is_error(&self) -> bool {
         match self {
             Self::Head(..)      => false,
+            Self::Redirect(..)  => false,
             Self::Data(..)      => false,
+            Self::Trailers(..)  => false,
+            Self::Progress(..)  => false,
+            Self::Readable      => false,
+            Self::Fallback      => false,
             Self::Done          => false,
-            Self::TimedOut      => true, // <-
-            Self::Aborted       => true, // <-
+            Self::TimedOut(..)  => true, // <-
+            Self::Error(..)     => true, // <-
             Self::UnknownHost   => true, // <-
-            Self::ProtocolError => true, // <-
+            Self::ProxyError(..) => true, // <-
+            Self::HeadTooLarge  => true, // <-
+            Self::BodyTooLarge  => true, // <-
+            Self::Upgraded(..)  => false,
+            Self::Resolved(..)  => false,
+            Self::Cancelled     => true, // <-
+            Self::Blocked(..)   => true, // <-
         }
     }
 
     /// Returns an appropriate error if `is_error` is true.
+    ///
+    /// The kind tells the failures apart: [`TimedOut`](io::ErrorKind::TimedOut) for timeouts,
+    /// [`NotFound`](io::ErrorKind::NotFound) for unknown hosts, [`PermissionDenied`](io::ErrorKind::PermissionDenied)
+    /// for blocked requests and [`Error::kind`] for errors.
     pub fn into_io_error(&self) -> Option<io::Error> {
         match self {
-            ResponseState::Aborted       => Some(io::Error::from(io::ErrorKind::ConnectionAborted)),
-            ResponseState::TimedOut      => Some(io::Error::from(io::ErrorKind::TimedOut)),
-            ResponseState::UnknownHost   => Some(io::Error::new(io::ErrorKind::Other, "unknown host")),
-            ResponseState::ProtocolError => Some(io::Error::new(io::ErrorKind::Other, "http protocol error")),
+            ResponseState::Error(err)    => Some(io::Error::new(err.kind(), err.clone())),
+            ResponseState::TimedOut(timeout) => Some(io::Error::new(io::ErrorKind::TimedOut, format!("request timed out: {:?}", timeout))),
+            ResponseState::UnknownHost   => Some(io::Error::new(io::ErrorKind::NotFound, "unknown host")),
+            ResponseState::ProxyError(status) => Some(io::Error::other(format!("proxy error: {} {}", status.code(), status.reason))),
+            ResponseState::HeadTooLarge  => Some(io::Error::new(io::ErrorKind::InvalidData, "response head too large")),
+            ResponseState::BodyTooLarge  => Some(io::Error::new(io::ErrorKind::InvalidData, "response body too large")),
+            ResponseState::Cancelled     => Some(io::Error::new(io::ErrorKind::ConnectionAborted, "the client was shut down")),
+            ResponseState::Blocked(blocked) => Some(io::Error::new(io::ErrorKind::PermissionDenied, format!("blocked by the request policy: {:?}", blocked))),
             _other => None
         }
     }
@@ -557,14 +2366,212 @@ impl ResponseState {
 impl fmt::Debug for ResponseState {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
-            Self::TimedOut => write!(f, "TimedOut"),
+            Self::TimedOut(timeout) => write!(f, "TimedOut({:?})", timeout),
             Self::Head(head) => write!(f, "Head({:?})", head),
+            Self::Redirect(redirect) => write!(f, "Redirect({:?})", redirect),
             Self::Data(data) => write!(f, "Data({} bytes)", data.len()),
+            Self::Trailers(trailers) => write!(f, "Trailers({:?})", trailers),
+            Self::Progress(progress) => write!(f, "Progress({:?})", progress),
+            Self::Readable => write!(f, "Readable"),
+            Self::Fallback => write!(f, "Fallback"),
             Self::Done => write!(f, "Done"),
-            Self::Aborted => write!(f, "Dead"),
+            Self::Error(err) => write!(f, "Error({:?})", err),
             Self::UnknownHost => write!(f, "UnknownHost"),
-            Self::ProtocolError => write!(f, "Error"),
+            Self::ProxyError(status) => write!(f, "ProxyError({} {})", status.code(), status.reason),
+            Self::HeadTooLarge => write!(f, "HeadTooLarge"),
+            Self::BodyTooLarge => write!(f, "BodyTooLarge"),
+            Self::Upgraded(connection) => write!(f, "Upgraded({:?})", connection),
+            Self::Resolved(addr, ttl) => write!(f, "Resolved({}, {:?})", addr, ttl),
+            Self::Cancelled => write!(f, "Cancelled"),
+            Self::Blocked(blocked) => write!(f, "Blocked({:?})", blocked),
+        }
+    }
+}
+
+/// How much of a body was transferred, see [`ResponseState::Progress`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Progress {
+    /// The bytes of the streamed request body that were sent.
+    /// The `total` is `None` if the body is sent chunked, the chunk framing isn't counted.
+    Upload { sent: u64, total: Option<u64> },
+    /// The bytes of the response body that were delivered as [`ResponseState::Data`].
+    /// The `total` is the `Content-Length`, it is `None` if the body is chunked or decompressed.
+    Download { received: u64, total: Option<u64> },
+}
+
+/// A step in the lifecycle of a request, see [`Client::set_event_hook`](crate::Client::set_event_hook).
+///
+/// A request that is retried or falls back to another address goes through the steps again.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ClientEvent {
+    /// The dns lookup started. Cached hosts and ip literals aren't looked up.
+    DnsStarted,
+    /// The dns lookup is done, this contains the preferred address.
+    DnsResolved(IpAddr),
+    /// Connecting to this address started. If there is a proxy, this is the address of the proxy.
+    Connecting(SocketAddr),
+    /// The tls handshake with the host is done.
+    TlsHandshakeDone,
+    /// All of the request was written, this contains how many bytes were written, including a streamed body.
+    RequestSent(u64),
+    /// The response head arrived, this contains its status code.
+    HeadReceived(StatusCode),
+    /// Part of the body was delivered as [`ResponseState::Data`], this contains its length.
+    DataReceived(usize),
+    /// The request is finished, this contains the kind of [`ResponseState::into_io_error`] if it failed.
+    Finished(Result<(), io::ErrorKind>),
+}
+
+/// Restricts which requests a [`Client`](crate::Client) sends, eg. if the urls come from users.
+///
+/// By default everything is allowed. A request that isn't allowed fails with [`ResponseState::Blocked`].
+///
+/// # Example
+///
+/// Protect against server-side request forgery.
+///
+/// ```rust
+/// let policy = RequestPolicy { require_tls: true, block_private_ips: true, ..Default::default() };
+/// client.set_policy(policy);
+/// ```
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct RequestPolicy {
+    /// Only allow `https` requests.
+    pub require_tls: bool,
+    /// Only allow requests to these hosts, they are compared ignoring the case.
+    pub allowed_hosts: Option<Vec<String>>,
+    /// Don't connect to loopback, private, link-local or other addresses that aren't public.
+    ///
+    /// The addresses are checked after the dns lookup, since any name can point to such an address.
+    /// If a request is sent through a proxy, the proxy resolves the host, so only ip literals can be checked.
+    pub block_private_ips: bool,
+}
+
+/// Why a request was blocked, see [`RequestPolicy`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Blocked {
+    /// The request doesn't use tls. See [`RequestPolicy::require_tls`].
+    Plain,
+    /// The host isn't allowed. See [`RequestPolicy::allowed_hosts`].
+    Host,
+    /// The host only has addresses that aren't public, this is one of them. See [`RequestPolicy::block_private_ips`].
+    Address(IpAddr),
+}
+
+/// Which timeout expired, see [`ResponseState::TimedOut`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Timeout {
+    /// The request took longer than its [`timeout`](RequestBuilder::timeout).
+    Total,
+    /// The dns lookup took too long, see [`RequestBuilder::dns_timeout`].
+    Dns,
+    /// Connecting to the host took too long, see [`RequestBuilder::connect_timeout`].
+    Connect,
+    /// The response head didn't arrive in time, see [`RequestBuilder::read_timeout`].
+    Read,
+    /// Nothing was received or sent for too long, see [`RequestBuilder::idle_timeout`].
+    Idle,
+    /// The client was drained and the request didn't finish before the deadline, see [`Client::drain`](crate::Client::drain).
+    Drain,
+}
+
+/// Why a request failed, see [`ResponseState::Error`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum Error {
+    /// Connecting to the host failed. This is the error of the last address that was tried.
+    Connect(io::ErrorKind),
+    /// The tls handshake failed, for example because the certificate is invalid.
+    #[cfg(feature = "tls")]
+    Tls(rustls::Error),
+    /// The dns server sent an invalid response.
+    Dns,
+    /// The response head is invalid, this includes the reply of a proxy.
+    ParseHead,
+    /// The response body is invalid, for example the chunked encoding or the compression is broken.
+    ParseBody,
+    /// The server closed the connection before the whole response head was received.
+    HeadIncomplete,
+    /// The server closed the connection before the whole response body was received.
+    BodyIncomplete,
+    /// The streamed request body couldn't be read or sent, see [`RequestBuilder::send_stream`].
+    Upload(io::ErrorKind),
+    /// Reading from or writing to the connection failed, for example because it was reset.
+    Io(io::ErrorKind),
+    /// The server answered an upgrade request with `101 Switching Protocols`, but not to the WebSocket protocol
+    /// or with the wrong `Sec-WebSocket-Accept`. See [`Client::upgrade`](crate::Client::upgrade).
+    Upgrade,
+    /// The server chose an application protocol other than HTTP/1, eg. `h2`. See [`Client::set_alpn_protocols`](crate::Client::set_alpn_protocols).
+    #[cfg(feature = "tls")]
+    Alpn(Vec<u8>),
+    /// An earlier request of the pipeline failed or was cancelled, so this one won't be answered.
+    /// See [`Client::send_pipeline`](crate::Client::send_pipeline).
+    Pipeline,
+}
+
+impl Error {
+
+    /// Returns the [`io::ErrorKind`] used by [`ResponseState::into_io_error`].
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            Self::Connect(kind)  => *kind,
+            #[cfg(feature = "tls")]
+            Self::Tls(..)        => io::ErrorKind::InvalidData,
+            Self::Dns            => io::ErrorKind::Other,
+            Self::ParseHead      => io::ErrorKind::InvalidData,
+            Self::ParseBody      => io::ErrorKind::InvalidData,
+            Self::HeadIncomplete => io::ErrorKind::UnexpectedEof,
+            Self::BodyIncomplete => io::ErrorKind::UnexpectedEof,
+            Self::Upload(kind)   => *kind,
+            Self::Io(kind)       => *kind,
+            Self::Upgrade        => io::ErrorKind::InvalidData,
+            #[cfg(feature = "tls")]
+            Self::Alpn(..)       => io::ErrorKind::Unsupported,
+            Self::Pipeline       => io::ErrorKind::ConnectionAborted,
+        }
+    }
+
+    /// Returns the error for a failed connection attempt, tls errors are recognized.
+    pub(crate) fn connect(err: &io::Error) -> Self {
+        Self::tls(err).unwrap_or(Self::Connect(err.kind()))
+    }
+
+    /// Returns the error for a failed read or write, tls errors are recognized.
+    pub(crate) fn io(err: &io::Error) -> Self {
+        Self::tls(err).unwrap_or(Self::Io(err.kind()))
+    }
+
+    /// Returns [`Error::Tls`] if `err` was caused by rustls.
+    #[cfg_attr(not(feature = "tls"), allow(unused_variables))]
+    pub(crate) fn tls(err: &io::Error) -> Option<Self> {
+        #[cfg(feature = "tls")]
+        if let Some(tls_err) = err.get_ref().and_then(|inner| inner.downcast_ref::<rustls::Error>()) {
+            return Some(Self::Tls(tls_err.clone()))
+        }
+        None
+    }
+
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Connect(kind)  => write!(f, "could not connect to the host: {}", kind),
+            #[cfg(feature = "tls")]
+            Self::Tls(err)       => write!(f, "tls error: {}", err),
+            Self::Dns            => write!(f, "invalid dns response"),
+            Self::ParseHead      => write!(f, "invalid response head"),
+            Self::ParseBody      => write!(f, "invalid response body"),
+            Self::HeadIncomplete => write!(f, "the connection was closed before the response head was received"),
+            Self::BodyIncomplete => write!(f, "the connection was closed before the response body was received"),
+            Self::Upload(kind)   => write!(f, "could not send the request body: {}", kind),
+            Self::Io(kind)       => write!(f, "connection error: {}", kind),
+            Self::Upgrade        => write!(f, "the server didn't switch to the websocket protocol"),
+            #[cfg(feature = "tls")]
+            Self::Alpn(protocol) => write!(f, "the server chose an unsupported protocol: {}", String::from_utf8_lossy(protocol)),
+            Self::Pipeline       => write!(f, "an earlier request of the pipeline failed"),
         }
     }
 }
 
+impl std::error::Error for Error {}
+
@@ -0,0 +1,63 @@
+//! Sending and receiving json bodies, with the `json` feature.
+
+use std::{fmt, io};
+use serde::Serialize;
+use crate::RequestBuilder;
+
+impl<'a> RequestBuilder<'a> {
+
+    /// Serialize the value and send it as an `application/json` body.
+    ///
+    /// The serialized body is owned by the request. This replaces the body set using
+    /// [`send`](RequestBuilder::send) and sets the `Content-Type` header, unless it was already set.
+    /// If serializing fails an [`InvalidInput`](io::ErrorKind::InvalidInput) error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let req = Request::post().host("example.com").json(&vec!["a", "b"])?;
+    /// ```
+    pub fn json(self, value: &impl Serialize) -> io::Result<Self> {
+        let body = serde_json::to_vec(value).map_err(|err| io::Error::new(io::ErrorKind::InvalidInput, err))?;
+        Ok(self.send_owned(body, "application/json"))
+    }
+
+}
+
+/// Why a json response body couldn't be read, see [`SimpleResponse::json`](crate::SimpleResponse::json).
+#[derive(Debug)]
+pub enum JsonError {
+    /// The `Content-Type` of the response is not json, this contains the content type if there is one.
+    ContentType(Option<String>),
+    /// The body is not valid json or doesn't match the expected type.
+    Deserialize(serde_json::Error),
+}
+
+impl fmt::Display for JsonError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ContentType(Some(content_type)) => write!(f, "the response is not json: {}", content_type),
+            Self::ContentType(None) => write!(f, "the response has no content type"),
+            Self::Deserialize(err) => write!(f, "invalid json: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for JsonError {}
+
+impl From<JsonError> for io::Error {
+    fn from(err: JsonError) -> Self {
+        io::Error::new(io::ErrorKind::InvalidData, err)
+    }
+}
+
+/// Deserializes the body if the content type of the response is `application/json` or ends with `+json`.
+pub(crate) fn from_body<T: serde::de::DeserializeOwned>(head: &crate::ResponseHead, body: &[u8]) -> Result<T, JsonError> {
+
+    if head.is_json() {
+        serde_json::from_slice(body).map_err(JsonError::Deserialize)
+    } else {
+        Err(JsonError::ContentType(head.get_header("Content-Type").map(str::to_string)))
+    }
+
+}
@@ -13,33 +13,74 @@
 //! Depending on how what you need you can choose between:
 //! - A [`Client`], which gives you full controll and is used with a [`mio::Poll`](https://docs.rs/mio/latest/mio/struct.Poll.html).
 //! - A [`SimpleClient`], which enables you to use the `async` ecosystem, still in a lightweight way.
+//! - A [`BlockingClient`], which just waits for the response without any executor.
 //!
 //! ### Supported features:
 //! - Plain HTTP requests
 //! - Secure HTTPS requests
-//! - Chunked transfer encoding
-//! - Nonblocking DNS lookup & HTTP requests
+//! - Chunked transfer encoding, also for streamed request bodies (see [`RequestBuilder::send_stream`])
+//!   and including trailers (see [`ResponseState::Trailers`])
+//! - Nonblocking DNS lookup & HTTP requests, over IPv4 and IPv6 (lookups on their own with [`Client::resolve`])
+//! - Keeping the DNS cache across restarts (see [`Client::export_dns_cache`])
 //! - Timeouts
+//! - Letting running requests finish before shutting down (see [`Client::drain`] and [`SimpleClient::close`])
+//! - Observing every step of a request, eg. for debugging (see [`Client::set_event_hook`])
+//! - Progress of uploads and downloads (opt-in, see [`RequestBuilder::progress`])
+//! - Reading the body into your own buffer (opt-in, see [`RequestBuilder::pull_body`])
+//! - Conditional requests using `ETag` and `Last-Modified` (see [`SimpleClient::fetch_conditional`])
+//! - Redirects with the resolved target, they aren't followed (opt-in, see [`RequestBuilder::report_redirects`])
+//! - Retrying failed requests (opt-in, see [`RequestBuilder::retry`])
+//! - Connection keep alive (opt-in, see [`Client::set_pooling`])
+//! - Opening connections ahead of time (see [`Client::preconnect`])
+//! - Pipelining `GET` and `HEAD` requests to the same host (see [`Client::send_pipeline`])
+//! - Percent encoding of query parameters (see [`RequestBuilder::query`])
+//! - Basic and Bearer authentication (see [`RequestBuilder::basic_auth`])
+//! - HTTP proxies, including `CONNECT` tunnels for HTTPS (see [`Client::with_proxy`])
+//! - HTTP over unix domain sockets (see [`RequestBuilder::unix_socket`])
+//! - Upgrading connections to WebSockets (see [`Client::upgrade`])
+//! - Restricting requests to public addresses, allowed hosts or HTTPS (see [`Client::set_policy`])
+//! - Gzip and deflate compression (with the `compression` feature)
+//! - Brotli compression (with the `brotli` feature)
+//! - Json bodies (with the `json` feature)
+//! - Text encodings other than utf8 (with the `encoding` feature)
 //! - Lightweight, runtime independent `async` reqests
 //! 
 //! ### Currently **not** implemented:
-//! - Connection keep alive
-//! - Compression (gzip etc.)
-//! - Different text encodings
-//! - Url percent encoding
+//! - Other compression formats
+//! - Percent encoding of the path, it has to be encoded already
 //! - Automatic redirects
 //! - Maybe more...
 //!
-//! The crate currently uses google's dns server (8.8.8.8) for dns lookups.
+//! The crate uses google's dns server (8.8.8.8) for dns lookups by default.
+//! A different server can be used with [`Client::with_dns_server`] and the system's configuration
+//! from `/etc/resolv.conf` can be used with [`Client::with_dns_config`] and [`DnsConfig::from_system`].
 //!
 //! # Features
 //!
 //! The `tls` default-feature enables the use of HTTPS using rustls.
+//! The `native-certs` feature also trusts the certificates in the system's trust store, on unix.
 //! The `async` default-feature enables the `SimpleClient` functionality.
+//! The `compression` feature enables decoding `gzip` and `deflate` compressed responses.
+//! The `brotli` feature enables decoding `br` compressed responses.
+//! The `json` feature enables sending and receiving json bodies using `serde_json`,
+//! see [`RequestBuilder::json`] and [`SimpleResponse::json`].
+//! The `encoding` feature enables decoding text bodies in the `charset` of the response using `encoding_rs`,
+//! see [`SimpleResponse::text`].
 //!
 
 mod util;
 mod dns;
+mod proxy;
+mod decoder;
+mod chunked;
+#[cfg(feature = "tls")]
+mod pem;
+#[cfg(feature = "tls")]
+mod verify;
+mod upgrade;
+mod blocking;
+#[cfg(feature = "json")]
+mod json;
 pub mod http;
 pub mod client;
 #[cfg(test)]
@@ -47,11 +88,18 @@ mod test;
 
 pub use {
     http::*,
-    client::*
+    client::*,
+    dns::DnsConfig,
+    proxy::{ProxyConfig, ProxyAuth},
+    upgrade::UpgradedConnection,
+    blocking::BlockingClient,
 };
 
-#[cfg(all(unix, feature = "async"))]
+#[cfg(feature = "json")]
+pub use json::JsonError;
+
+#[cfg(feature = "async")]
 pub mod simple;
 
-#[cfg(all(unix, feature = "async"))]
+#[cfg(feature = "async")]
 pub use simple::*;
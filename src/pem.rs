@@ -0,0 +1,130 @@
+//! Parsing PEM encoded certificates and keys, with the `tls` feature.
+
+use std::io;
+
+/// A `-----BEGIN <label>-----` section of a PEM file.
+pub(crate) struct Section {
+    pub label: String,
+    pub data: Vec<u8>,
+}
+
+/// Parses all sections of a PEM file, text outside of the sections is ignored.
+///
+/// Returns an [`InvalidData`](io::ErrorKind::InvalidData) error if a section is not terminated or not valid base64.
+pub(crate) fn parse(pem: &[u8]) -> io::Result<Vec<Section>> {
+
+    let text = std::str::from_utf8(pem).map_err(|_err| invalid("pem file is not valid utf-8"))?;
+    let mut lines = text.lines().map(str::trim);
+    let mut sections = Vec::new();
+
+    while let Some(line) = lines.next() {
+        let Some(label) = line.strip_prefix("-----BEGIN ").and_then(|rest| rest.strip_suffix("-----")) else { continue };
+        let end = format!("-----END {}-----", label);
+        let mut encoded = String::new();
+        loop {
+            match lines.next() {
+                Some(line) if line == end => break,
+                Some(line) => encoded.push_str(line),
+                None => return Err(invalid("unterminated pem section")),
+            }
+        }
+        let data = base64_decode(encoded.as_bytes()).ok_or_else(|| invalid("invalid base64 in pem section"))?;
+        sections.push(Section { label: label.to_string(), data });
+    }
+
+    Ok(sections)
+
+}
+
+/// Returns all certificates of a PEM bundle, there has to be at least one.
+pub(crate) fn certificates(pem: &[u8]) -> io::Result<Vec<rustls::Certificate>> {
+    let certificates: Vec<_> = parse(pem)?.into_iter()
+        .filter(|section| section.label == "CERTIFICATE")
+        .map(|section| rustls::Certificate(section.data))
+        .collect();
+    if certificates.is_empty() {
+        return Err(invalid("no certificates found"))
+    }
+    Ok(certificates)
+}
+
+/// Returns the first private key of a PEM file, in PKCS#8, PKCS#1 (rsa) or SEC1 (ec) format.
+pub(crate) fn private_key(pem: &[u8]) -> io::Result<rustls::PrivateKey> {
+    for section in parse(pem)? {
+        match section.label.as_str() {
+            "PRIVATE KEY" | "RSA PRIVATE KEY" | "EC PRIVATE KEY" => return Ok(rustls::PrivateKey(section.data)),
+            "ENCRYPTED PRIVATE KEY" => return Err(invalid("encrypted private keys are not supported")),
+            _other => continue,
+        }
+    }
+    Err(invalid("no private key found"))
+}
+
+/// Returns the certificates in the system's trust store.
+///
+/// The bundle in `SSL_CERT_FILE` or the first bundle found at a well known location is used,
+/// certificates that can't be read are skipped. There are none on non-unix systems.
+#[cfg(feature = "native-certs")]
+pub(crate) fn native_certificates() -> Vec<rustls::Certificate> {
+
+    // the locations used by the common linux distributions and the BSDs
+    const BUNDLES: &[&str] = &[
+        "/etc/ssl/certs/ca-certificates.crt",
+        "/etc/pki/tls/certs/ca-bundle.crt",
+        "/etc/ssl/ca-bundle.pem",
+        "/etc/pki/ca-trust/extracted/pem/tls-ca-bundle.pem",
+        "/etc/ssl/cert.pem",
+        "/usr/local/share/certs/ca-root-nss.crt",
+        "/usr/local/etc/ssl/cert.pem",
+    ];
+
+    if !cfg!(unix) {
+        return Vec::new()
+    }
+
+    let env_bundle = std::env::var_os("SSL_CERT_FILE").map(std::path::PathBuf::from);
+    let bundle = env_bundle.into_iter().chain(BUNDLES.iter().map(std::path::PathBuf::from))
+        .find_map(|path| std::fs::read(path).ok());
+
+    bundle.and_then(|bundle| certificates(&bundle).ok()).unwrap_or_default()
+
+}
+
+/// Decodes standard base64, the padding is optional.
+fn base64_decode(input: &[u8]) -> Option<Vec<u8>> {
+
+    let mut output = Vec::with_capacity(input.len() / 4 * 3);
+    let mut bits = 0u32;
+    let mut count = 0;
+
+    for byte in input.iter().copied().take_while(|byte| *byte != b'=') {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _other => return None,
+        };
+        bits = bits << 6 | value as u32;
+        count += 1;
+        if count == 4 {
+            output.extend_from_slice(&bits.to_be_bytes()[1..]);
+            (bits, count) = (0, 0);
+        }
+    }
+
+    match count {
+        0 => (),
+        2 => output.push((bits >> 4) as u8),
+        3 => output.extend_from_slice(&((bits >> 2) as u16).to_be_bytes()),
+        _invalid => return None,
+    }
+
+    Some(output)
+
+}
+
+fn invalid(reason: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, reason)
+}
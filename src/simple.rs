@@ -1,10 +1,17 @@
 
 //! Sending requests using `async`.
 
-use std::{fmt, io::{self, Read, Write}, string, thread, sync::{Arc, Mutex}, collections::{HashMap, VecDeque}, task::{self, Waker, Poll}, future::{self, Future}, pin::Pin};
-use futures_lite::AsyncReadExt;
+use std::{io::{self, Read}, net::{IpAddr, SocketAddr}, thread, sync::{Arc, Mutex, MutexGuard, mpsc}, collections::{HashMap, VecDeque}, task::{self, Waker, Poll}, future::{self, Future}, pin::Pin, mem::take, time::{Duration, Instant}};
+use futures_lite::{AsyncReadExt, AsyncWriteExt};
+use futures_io::{AsyncRead, AsyncBufRead, AsyncWrite};
 
-use crate::{Client, ResponseHead, ResponseState, RawRequest, util::wouldblock};
+use crate::{Client, ClientBuilder, DnsConfig, ProxyConfig, OwnedHeader, Method, Progress, ReqId, RequestBuilder, ResponseHead, ResponseState, RawRequest, SimpleResponse, Timings, Validators};
+
+/// How many bytes of an upload are buffered at most, before waiting for the server to receive them.
+const MAX_UPLOAD_BUFFER: usize = 64 * 1024;
+
+/// How many bytes of a response body are buffered, before the client stops reading until the [`BodyReader`] caught up.
+const MAX_BODY_BUFFER: usize = 64 * 1024;
 
 /// A simpler HTTP client that handles I/O events for you.
 ///
@@ -12,148 +19,382 @@ use crate::{Client, ResponseHead, ResponseState, RawRequest, util::wouldblock};
 /// The client is backed by a single reaper thread, that egerly polls `mio` for new event and processes them.
 /// Because of this design the client is fully runtime independent. It even works without any runtime. You could just `block_on` the future.
 ///
-/// Sadly, because the `mio::unix::pipe` module is used here, the client currently only runs on 64 bit unix systems.
-///
 /// # Example
 ///
 /// It is really easy to send a single request.
 ///
 /// ```rust
-/// let mut client = SimpleClient::new()?;
+/// let client = SimpleClient::new()?;
 /// let resp = client.send(Request::get("example.com")).await?;
 /// let body_str = String::from_utf8(resp.body); // note: not all websites use UTF-8!
 /// println!("{}", body_str);
 /// ```
 ///
-/// # Note
-/// Please note that the client currently just panics in a lot of fatal error cases.
-/// Most errors should be caught when calling `SimpleClient::new` though!
-/// I plan on adressing this issue soon, so TODO: Fix hard panics on mio/pipe error and error on next stream/send instead
+/// Dropping the future or the [`BodyReader`] before the response is complete cancels the request.
+///
+/// The client is cheap to clone, all clones share the same reaper thread. Requests can be sent
+/// from different tasks at once, the reaper thread shuts down once the last clone is dropped.
+/// Use [`SimpleClient::close`] to let the running requests finish first.
+///
+/// # Errors
+/// A request that can't be sent, eg. because the host is invalid, resolves to an error.
+/// If polling fails, the reaper thread stops and all requests, including the ones sent later, fail with that error.
+#[derive(Clone)]
 pub struct SimpleClient {
-    reaper: Option<thread::JoinHandle<()>>,
-    sender: mio::unix::pipe::Sender,
+    reaper: Arc<Reaper>, // shared by all clones
+}
+
+/// The reaper thread, it is shut down once this is dropped.
+struct Reaper {
+    thread: Option<thread::JoinHandle<()>>,
+    notifier: Arc<Notifier>, // shared with the responses, so they can cancel their request
 }
 
-impl Drop for SimpleClient {
+impl Drop for Reaper {
     fn drop(&mut self) {
-        self.shutdown();
-        self.reaper.take().unwrap().join().unwrap();
+        // indicates to the reaper thread that it should shut itself down,
+        // this fails if it already stopped because of an error
+        let _ignored = self.notifier.send(Message::Shutdown);
+        let _ignored = self.thread.take().unwrap().join();
     }
 }
 
 struct SimpleRequestState {
     pub request: Option<RawRequest>,
+    pub lookup: Option<String>, // the host to look up instead of sending a request, see `SimpleClient::lookup`
     pub resps: VecDeque<ResponseState>,
     pub waker: Option<Waker>,
+    pub trailers: Option<Vec<OwnedHeader>>,
+    pub id: Option<ReqId>, // set once the request was sent
+    pub finished: bool,
+    pub cancelled: bool,
+    pub paused: bool, // too much of the body is buffered, see `Client::pause`
+    pub resuming: bool, // the reader asked the reaper thread to resume the request
+    pub failed: Option<io::Error>, // the reaper thread couldn't handle the request, this is returned after the responses
+    pub timings: Option<Timings>, // set once the request finished
+}
+
+impl SimpleRequestState {
+
+    /// How many bytes of the body weren't read yet.
+    fn buffered(&self) -> usize {
+        self.resps.iter().map(|resp| if let ResponseState::Data(data) = resp { data.len() } else { 0 }).sum()
+    }
+
+    /// Fails the request with an error of the reaper thread, instead of waiting for more responses.
+    fn fail(&mut self, err: &io::Error) {
+        self.failed = Some(io::Error::new(err.kind(), err.to_string()));
+        self.finished = true;
+        if let Some(ref waker) = self.waker {
+            waker.wake_by_ref();
+        }
+    }
+
+    /// The error of the reaper thread, it is returned every time it is asked for.
+    fn error(&self) -> Option<io::Error> {
+        self.failed.as_ref().map(|err| io::Error::new(err.kind(), err.to_string()))
+    }
+
 }
 
 impl SimpleClient {
 
     const CLIENT:   mio::Token = mio::Token(0);
-    const RECEIVER: mio::Token = mio::Token(1);
+    const WAKER:    mio::Token = mio::Token(1);
     const STARTID: usize = 2;
 
     /// Creates a new client
     ///
     /// An error is a fatal failure and probably means that the system doesn't support all necessary functionality.
     pub fn new() -> io::Result<Self> {
+        Self::with_client(Client::new(Self::CLIENT))
+    }
 
-        let mut io = mio::Poll::new()?;
-        let (sender, mut receiver) = mio::unix::pipe::new()?;
+    /// Creates a new client that uses a custom dns server.
+    ///
+    /// See [`Client::with_dns_server`].
+    pub fn with_dns_server(server: SocketAddr) -> io::Result<Self> {
+        Self::with_client(Client::with_dns_server(Self::CLIENT, server))
+    }
 
-        // io.registry().register(&mut sender, Self::SENDER, mio::Interest::WRITABLE);
-        // ^^^ we don't register the sender, since we will write to it in blocking mode 
-        sender.set_nonblocking(false).unwrap();
+    /// Creates a new client that uses a custom dns configuration.
+    ///
+    /// See [`Client::with_dns_config`].
+    pub fn with_dns_config(config: DnsConfig) -> io::Result<Self> {
+        Self::with_client(Client::with_dns_config(Self::CLIENT, config))
+    }
 
-        io.registry().register(&mut receiver, Self::RECEIVER, mio::Interest::READABLE)?;
+    /// Creates a new client that sends all requests through an HTTP proxy.
+    ///
+    /// See [`Client::with_proxy`].
+    pub fn with_proxy(proxy: ProxyConfig) -> io::Result<Self> {
+        Self::with_client(Client::with_proxy(Self::CLIENT, proxy))
+    }
 
-        Ok(Self {
-            reaper: Some(thread::spawn(move || {
+    /// Creates a new client with all options in one place.
+    ///
+    /// The dns token of the builder is ignored, since the client uses its own `mio::Poll`.
+    /// See [`Client::builder`].
+    pub fn with_config(config: ClientBuilder) -> io::Result<Self> {
+        Self::with_client(config.dns_token(Self::CLIENT).build())
+    }
 
-                let mut client = Client::new(Self::CLIENT);
-                let mut next_id = Self::STARTID;
+    /// Creates a new client that sends at most `max_concurrent` requests at once, and `max_per_host` to the same host.
+    ///
+    /// The other requests wait inside the client until a slot is free. See [`Client::set_max_concurrent`]
+    /// and [`Client::set_max_per_host`].
+    pub fn with_connection_limits(max_concurrent: Option<usize>, max_per_host: Option<usize>) -> io::Result<Self> {
+        let mut client = Client::new(Self::CLIENT);
+        client.set_max_concurrent(max_concurrent);
+        client.set_max_per_host(max_per_host);
+        Self::with_client(client)
+    }
 
-                let mut requests = HashMap::with_capacity(8);
+    /// Creates a new client with custom limits for the response head.
+    ///
+    /// See [`Client::set_max_headers`] and [`Client::set_max_head_size`].
+    pub fn with_head_limits(max_headers: usize, max_head_size: usize) -> io::Result<Self> {
+        let mut client = Client::new(Self::CLIENT);
+        client.set_max_headers(max_headers);
+        client.set_max_head_size(max_head_size);
+        Self::with_client(client)
+    }
 
-                loop {
+    /// Creates a new client that also trusts the certificates in a PEM bundle.
+    ///
+    /// See [`Client::add_root_certificate_pem`].
+    #[cfg(feature = "tls")]
+    pub fn with_root_certificate_pem(pem: &[u8]) -> io::Result<Self> {
+        let mut client = Client::new(Self::CLIENT);
+        client.add_root_certificate_pem(pem)?;
+        Self::with_client(client)
+    }
 
-                    let mut events = mio::Events::with_capacity(32);
-                    io.poll(&mut events, None).unwrap();
+    /// Creates a new client with a custom [`ClientConfig`](rustls::ClientConfig).
+    ///
+    /// See [`Client::with_tls_config`].
+    #[cfg(feature = "tls")]
+    pub fn with_tls_config(tls_config: std::sync::Arc<rustls::ClientConfig>) -> io::Result<Self> {
+        Self::with_client(Client::with_tls_config(Self::CLIENT, tls_config))
+    }
+
+    /// Creates a new client that authenticates itself using a client certificate.
+    ///
+    /// See [`Client::with_client_auth`].
+    #[cfg(feature = "tls")]
+    pub fn with_client_auth(cert_pem: &[u8], key_pem: &[u8]) -> io::Result<Self> {
+        Self::with_client(Client::with_client_auth(Self::CLIENT, cert_pem, key_pem)?)
+    }
+
+    /// Creates a new client that accepts any certificate, this is unsafe for anything but development.
+    ///
+    /// See [`Client::danger_accept_invalid_certs`].
+    #[cfg(feature = "tls")]
+    pub fn danger_accept_invalid_certs() -> io::Result<Self> {
+        Self::with_client(Client::danger_accept_invalid_certs(Self::CLIENT))
+    }
+
+    /// Creates a new client that accepts certificates issued for another host, this is unsafe for anything but development.
+    ///
+    /// See [`Client::danger_accept_invalid_hostnames`].
+    #[cfg(feature = "tls")]
+    pub fn danger_accept_invalid_hostnames() -> io::Result<Self> {
+        Self::with_client(Client::danger_accept_invalid_hostnames(Self::CLIENT))
+    }
+
+    fn with_client(mut client: Client) -> io::Result<Self> {
+
+        let mut io = mio::Poll::new()?;
+        let (sender, receiver) = mpsc::channel();
+        let waker = mio::Waker::new(io.registry(), Self::WAKER)?;
+
+        let thread = thread::spawn(move || {
+
+            let mut next_id = Self::STARTID;
+
+            let mut requests = HashMap::with_capacity(8);
+            let mut closing = Vec::new(); // see `SimpleClient::close`
 
-                    'events: for event in events.iter() {
+            loop {
 
-                        if event.token() == Self::RECEIVER {
+                let mut events = mio::Events::with_capacity(32);
+                // wake up in time for the next deadline of a request
+                match io.poll(&mut events, client.timeout()) {
+                    Ok(()) => (),
+                    Err(err) if err.kind() == io::ErrorKind::Interrupted => continue,
+                    Err(err) => {
+                        let _ignored = client.shutdown(&io);
+                        return Self::fail_all(&receiver, requests.into_values(), &err)
+                    },
+                }
 
-                            loop {
+                'events: for event in events.iter() {
 
-                                let mut buff = [0; 8];
+                    if event.token() == Self::WAKER {
 
-                                match receiver.read(&mut buff) {
-                                    Ok(_bytes_read) => assert!(_bytes_read == 8),
-                                    Err(ref err) if wouldblock(err) => break 'events,
-                                    Err(err) => panic!("{}", err),
-                                };
+                        loop {
 
-                                // writing all zeroes signals that we should shutdown
+                            let request_state = match receiver.try_recv() {
+                                Ok(Message::Request(request_state)) => request_state,
+                                Ok(Message::Close(deadline, closer)) => {
+                                    client.drain(deadline);
+                                    closing.push(closer);
+                                    continue
+                                },
                                 // we shut down without waiting for any further events
-                                if buff == [0; 8] {
-                                    return
-                                };
+                                Ok(Message::Shutdown) | Err(mpsc::TryRecvError::Disconnected) => return Self::shutdown(&mut client, &io, requests),
+                                Err(mpsc::TryRecvError::Empty) => break 'events,
+                            };
+
+                            let mut guard = request_state.lock().unwrap();
+
+                            // the response was dropped, this may arrive before or after the request was sent
+                            if guard.cancelled {
+                                if let Some(id) = guard.id.take() {
+                                    // nobody waits for the request anymore, so an error can be ignored
+                                    let _ignored = client.cancel(&io, id);
+                                    requests.remove(&id);
+                                }
+                                continue
+                            }
 
-                                let request_state = unsafe { Arc::from_raw(
-                                    u64::from_ne_bytes(buff) as *mut Mutex<SimpleRequestState>
-                                ) };
-                                let mut guard = request_state.lock().unwrap();
+                            // the reader caught up with the buffered body
+                            if guard.resuming {
+                                guard.resuming = false;
+                                guard.paused = false;
+                                if let Some(id) = guard.id {
+                                    if let Err(err) = client.resume(&io, id) {
+                                        let _ignored = client.cancel(&io, id);
+                                        requests.remove(&id);
+                                        guard.fail(&err);
+                                    }
+                                }
+                                continue
+                            }
 
+                            let result = if let Some(host) = guard.lookup.take() {
+                                client.resolve(&io, &host, None)
+                            } else if let Some(request) = guard.request.take() {
                                 let token = next_id;
                                 next_id += 1;
+                                client.send_raw(&io, mio::Token(token), request)
+                            } else {
+                                // an upload has new data, the client is pumped below anyway
+                                continue
+                            };
 
-                                let request = guard.request.take().unwrap();
-                                let id = client.send(&io, mio::Token(token), request).unwrap(); // todo: can someting be done about all these unwraps
+                            // eg. the host is invalid, this only fails this request
+                            let id = match result {
+                                Ok(id) => id,
+                                Err(err) => { guard.fail(&err); continue },
+                            };
+                            guard.id = Some(id);
 
-                                drop(guard);
+                            drop(guard);
 
-                                requests.insert(id, request_state);
+                            requests.insert(id, request_state);
 
-                            }
+                        }
+
+                    }                        
+
+                }
+
+                // we can't tell which request an error belongs to
+                let resps = match client.pump(&io, &events) {
+                    Ok(resps) => resps,
+                    Err(err) => {
+                        let _ignored = client.shutdown(&io);
+                        return Self::fail_all(&receiver, requests.into_values(), &err)
+                    },
+                };
 
-                        }                        
+                for resp in resps {
 
+                    // the request just goes on with another address, the progress is tracked by the `BodyReader`
+                    if matches!(resp.state, ResponseState::Fallback | ResponseState::Progress(..)) {
+                        continue
                     }
 
-                    for resp in client.pump(&io, &events).unwrap() {
+                    let is_finished = resp.state.is_finished();
 
-                        let is_finished = resp.state.is_finished();
+                    let Some(request_state) = requests.get(&resp.id) else { continue };
+                    let mut guard = request_state.lock().unwrap();
+                    match resp.state {
+                        // the trailers are handed over by the `BodyReader` once the body was read
+                        ResponseState::Trailers(trailers) => guard.trailers = Some(trailers),
+                        other => guard.resps.push_back(other),
+                    }
+                    guard.finished = is_finished;
+                    if is_finished {
+                        guard.timings = client.timings(resp.id);
+                    }
+                    // stop reading the body until the reader caught up
+                    if !is_finished && !guard.paused && guard.buffered() >= MAX_BODY_BUFFER {
+                        guard.paused = client.pause(resp.id);
+                    }
+                    if let Some(ref waker) = guard.waker {
+                        waker.wake_by_ref();
+                    }
+                    drop(guard);
 
-                        let request_state = requests.get(&resp.id).unwrap();
-                        let mut guard = request_state.lock().unwrap();
-                        guard.resps.push_back(resp.state);
-                        if let Some(ref waker) = guard.waker {
-                            waker.wake_by_ref();
-                        }
-                        drop(guard);
+                    if is_finished {
+                        requests.remove(&resp.id);
+                    }
 
-                        if is_finished {
-                            requests.remove(&resp.id);
-                        }
+                };
 
-                    };
-                    
+                // the client was closed and all of its requests finished
+                if client.is_idle() {
+                    closing.clear();
                 }
-                
-            })),
-            sender
-        })
+            
+            }
+        
+        });
+
+        Ok(Self { reaper: Arc::new(Reaper { thread: Some(thread), notifier: Arc::new(Notifier { sender, waker }) }) })
+
+    }
 
+    /// Shuts the client down before the reaper thread stops, the requests that are still running are cancelled.
+    fn shutdown(client: &mut Client, io: &mio::Poll, requests: HashMap<ReqId, Arc<Mutex<SimpleRequestState>>>) {
+        // the sockets are closed once the thread exits anyway, so an error can be ignored
+        let resps = client.shutdown(io).unwrap_or_default();
+        for resp in resps {
+            let Some(request_state) = requests.get(&resp.id) else { continue };
+            let mut guard = request_state.lock().unwrap();
+            guard.resps.push_back(resp.state);
+            guard.finished = true;
+            if let Some(ref waker) = guard.waker {
+                waker.wake_by_ref();
+            }
+        }
+    }
+
+    /// Fails all requests that are handled by the reaper thread or were just handed to it, before it stops.
+    fn fail_all(receiver: &mpsc::Receiver<Message>, requests: impl Iterator<Item = Arc<Mutex<SimpleRequestState>>>, err: &io::Error) {
+        let queued = receiver.try_iter().filter_map(|message| match message {
+            Message::Request(request_state) => Some(request_state),
+            Message::Close(..) | Message::Shutdown => None,
+        });
+        for request_state in requests.chain(queued) {
+            request_state.lock().unwrap().fail(err);
+        }
     }
 
     /// Send a single request.
     ///
     /// This method will send a single request.
-    /// The returned future does not borrow `self`.
-    pub fn send(&mut self, input: impl Into<RawRequest>) -> impl Future<Output = io::Result<SimpleResponse<Vec<u8>>>> {
+    /// The returned future owns everything it needs, so it can be spawned or stored alongside the client.
+    pub fn send(&self, input: impl TryInto<RawRequest, Error = io::Error>) -> impl Future<Output = io::Result<SimpleResponse<Vec<u8>>>> + Send + 'static {
+        self.send_formatted(input.try_into())
+    }
 
-        let future = self.stream(input);
+    /// Sends the request unless it couldn't be formatted, see [`SimpleClient::send`].
+    fn send_formatted(&self, request: io::Result<RawRequest>) -> impl Future<Output = io::Result<SimpleResponse<Vec<u8>>>> + Send + 'static {
+
+        let future = Self::response(request.map(|request| self.start(request)));
 
         async move {
             let mut response = future.await?;
@@ -161,148 +402,713 @@ impl SimpleClient {
             response.body.read_to_end(&mut buff).await?;
             Ok(SimpleResponse {
                 head: response.head,
+                timings: response.body.timings(),
                 body: buff,
             })
         }
 
     }
 
+    /// Send many requests at the same time and wait until all of them are finished.
+    ///
+    /// The results are in the same order as the requests, a failed request doesn't affect the others.
+    /// With a `limit`, at most that many requests are sent at once, the next one is sent once another one finished.
+    /// Otherwise all of them are sent right away.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let requests = ["/a", "/b", "/c"].map(|path| Request::get().host("example.com").path(path));
+    /// for result in client.many(requests, Some(2)).await {
+    ///     println!("{:?}", result?.head.status);
+    /// }
+    /// ```
+    pub fn many(&self, requests: impl IntoIterator<Item = impl TryInto<RawRequest, Error = io::Error>>, limit: Option<usize>) -> impl Future<Output = Vec<io::Result<SimpleResponse<Vec<u8>>>>> + Send + 'static {
+
+        type Sending = Pin<Box<dyn Future<Output = io::Result<SimpleResponse<Vec<u8>>>> + Send>>;
+
+        // the requests are formatted right away, so the future doesn't borrow them
+        let mut queued: VecDeque<io::Result<RawRequest>> = requests.into_iter().map(TryInto::try_into).collect();
+        let mut results: Vec<Option<io::Result<SimpleResponse<Vec<u8>>>>> = queued.iter().map(|_| None).collect();
+        let limit = limit.unwrap_or(usize::MAX).max(1);
+        let client = self.clone();
+
+        let mut sending: Vec<(usize, Sending)> = Vec::new();
+        let mut next = 0;
+
+        let mut start = move |sending: &mut Vec<(usize, Sending)>| {
+            while sending.len() < limit {
+                let Some(request) = queued.pop_front() else { break };
+                sending.push((next, Box::pin(client.send_formatted(request))));
+                next += 1;
+            }
+        };
+
+        start(&mut sending);
+
+        future::poll_fn(move |ctx| {
+            loop {
+                let before = sending.len();
+                sending.retain_mut(|(idx, future)| match future.as_mut().poll(ctx) {
+                    Poll::Ready(result) => { results[*idx] = Some(result); false },
+                    Poll::Pending => true,
+                });
+                if sending.len() == before {
+                    break
+                }
+                // the finished requests make room for the next ones, which are polled right away
+                start(&mut sending);
+            }
+            if sending.is_empty() {
+                Poll::Ready(results.drain(..).map(Option::unwrap).collect())
+            } else {
+                Poll::Pending
+            }
+        })
+
+    }
+
+    /// Download a single request into a writer, returning the head and the number of bytes written.
+    ///
+    /// The body is written chunk by chunk as it is received, so it is never buffered completely.
+    /// If the status isn't `2xx`, nothing is written and the body is discarded. Check the status
+    /// of the returned head and use [`SimpleClient::stream`] if you need the body of such a response.
+    ///
+    /// If writing fails, the request is cancelled and the error is returned.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let file = async_fs::File::create("artifact.tar.gz").await?;
+    /// let (head, written) = client.download(Request::get().host("example.com").path("artifact.tar.gz"), file).await?;
+    /// ```
+    pub fn download(&self, input: impl TryInto<RawRequest, Error = io::Error>, mut writer: impl AsyncWrite + Unpin) -> impl Future<Output = io::Result<(ResponseHead, u64)>> {
+
+        let future = self.stream(input);
+
+        async move {
+            let mut response = future.await?;
+            // dropping the reader cancels the request
+            if !response.head.status.is_success() {
+                return Ok((response.head, 0))
+            }
+            let written = response.body.write_into(&mut writer).await?;
+            Ok((response.head, written))
+        }
+
+    }
+
+    /// Send a single request, unless the cached response is still fresh.
+    ///
+    /// The request is sent with the [`Validators`] of the cached response, so the server answers
+    /// `304 Not Modified` without a body if the resource didn't change. Without `cached` this works like [`SimpleClient::send`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let validators = cached.head.validators();
+    /// match client.fetch_conditional(Request::get().host("example.com").path("/feed.xml"), validators.as_ref()).await? {
+    ///     Conditional::Fresh(resp) => cached = resp,
+    ///     Conditional::NotModified(..) => (),
+    /// }
+    /// ```
+    pub fn fetch_conditional<'a>(&self, request: RequestBuilder<'a>, cached: Option<&'a Validators>) -> impl Future<Output = io::Result<Conditional>> + Send + 'static {
+
+        let request = match cached {
+            Some(validators) => request.validators(validators),
+            None => request,
+        };
+        let future = self.send_formatted(request.try_into());
+
+        async move {
+            let response = future.await?;
+            if response.head.status.code() == 304 {
+                Ok(Conditional::NotModified(response.head))
+            } else {
+                Ok(Conditional::Fresh(response))
+            }
+        }
+
+    }
+
+    /// Send a single request and only wait for the head of the response.
+    ///
+    /// The body is never downloaded, see [`RequestBuilder::discard_body`]. This is useful for
+    /// health checks or link validation, when servers don't handle `HEAD` requests well.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let head = client.status(Request::get().host("example.com").path("large.iso")).await?;
+    /// println!("{}", head.status.code());
+    /// ```
+    pub fn status(&self, input: impl TryInto<RawRequest, Error = io::Error>) -> impl Future<Output = io::Result<ResponseHead>> + Send + 'static {
+
+        let future = Self::response(input.try_into().map(|mut request| {
+            request.discard_body = true;
+            self.start(request)
+        }));
+
+        async move {
+            Ok(future.await?.head)
+        }
+
+    }
+
     /// Stream a single request.
     ///
     /// This method will send a single request and return a response once the
     /// [`ResponseHead`] has been transmitted.
     /// The response will contain a [`BodyReader`] as the `body` which implements
-    /// the [`AsyncRead`](futures_io::AsyncRead) trait.
+    /// the [`AsyncRead`](futures_io::AsyncRead) trait and is a [`Stream`](futures_lite::Stream) of body pieces.
     ///
     /// You can receive large responses packet-by-packet using this method.
-    pub fn stream<'d>(&'d mut self, input: impl Into<RawRequest>) -> impl Future<Output = io::Result<SimpleResponse<BodyReader>>> {
+    /// If the body isn't read, the client stops receiving it once 64 KiB are buffered.
+    ///
+    /// The request is sent right away, the returned future only waits for the response.
+    /// Like the one of [`SimpleClient::send`] it can be spawned.
+    pub fn stream(&self, input: impl TryInto<RawRequest, Error = io::Error>) -> impl Future<Output = io::Result<SimpleResponse<BodyReader>>> + Send + 'static {
+        Self::response(input.try_into().map(|request| self.start(request)))
+    }
+
+    /// Stream a single request, uploading the body from an [`AsyncRead`].
+    ///
+    /// With a known `len` the `Content-Length` header is sent, otherwise the body is sent
+    /// using chunked transfer encoding. See [`RequestBuilder::send_stream`].
+    /// Only a limited amount of the body is buffered, the rest is read as the server receives it.
+    ///
+    /// The body is uploaded while the returned future is polled. If reading the body fails,
+    /// the future resolves to an error. Otherwise this works like [`SimpleClient::stream`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let file = async_fs::File::open("upload.bin").await?;
+    /// let len = file.metadata().await?.len();
+    /// let resp = client.stream_upload(Request::post().host("example.com"), file, Some(len)).await?;
+    /// ```
+    pub fn stream_upload(&self, request: RequestBuilder<'_>, body: impl AsyncRead + Send + 'static, len: Option<u64>) -> impl Future<Output = io::Result<SimpleResponse<BodyReader>>> {
+
+        let pipe = Arc::new(Mutex::new(UploadPipe {
+            buffer: VecDeque::new(),
+            closed: false,
+            failed: false,
+            waker: None,
+        }));
+
+        let reader = request.send_stream(UploadReader { pipe: Arc::clone(&pipe) }, len).try_into()
+            .map(|request| self.start(request));
+
+        let request_state = reader.as_ref().ok().map(|reader| Arc::clone(&reader.request_state));
+        let notifier = Arc::clone(&self.reaper.notifier);
+
+        let upload = async move {
+
+            // the request couldn't be formatted, so there is nothing to upload
+            let Some(request_state) = request_state else { return };
+
+            let mut body = Box::pin(body);
+            let mut chunk = vec![0; MAX_UPLOAD_BUFFER / 4];
+
+            loop {
+
+                // wait until the reaper thread took some of the data, so a slow server doesn't make us buffer everything
+                future::poll_fn(|ctx| {
+                    let mut guard = pipe.lock().unwrap();
+                    if guard.buffer.len() < MAX_UPLOAD_BUFFER {
+                        Poll::Ready(())
+                    } else {
+                        guard.waker = Some(ctx.waker().clone());
+                        Poll::Pending
+                    }
+                }).await;
 
-        let request = input.into();
+                let result = body.read(&mut chunk).await;
+
+                let mut guard = pipe.lock().unwrap();
+                match result {
+                    Ok(0) => guard.closed = true,
+                    Ok(num) => guard.buffer.extend(&chunk[..num]),
+                    Err(..) => guard.failed = true,
+                }
+                let done = guard.closed || guard.failed;
+                drop(guard);
+
+                // the reaper thread doesn't know there is new data otherwise
+                let _ignored = notifier.request(&request_state);
+
+                if done { break }
+
+            }
+
+        };
+
+        // the response can't arrive before the whole body was sent, so the upload
+        // only has to be driven until then
+        futures_lite::future::or(Self::response(reader), async move {
+            upload.await;
+            future::pending().await
+        })
+
+    }
+
+    /// Stops accepting new requests and waits until the running ones finished, but at most for `grace`.
+    ///
+    /// Requests sent afterwards fail right away. The ones that didn't finish in time fail with a
+    /// [`TimedOut`](io::ErrorKind::TimedOut) error, see [`Client::drain`]. This affects all clones of the client.
+    /// Dropping the client without closing it first cancels the requests that are still running.
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// client.close(Duration::from_secs(10)).await;
+    /// ```
+    pub fn close(&self, grace: Duration) -> impl Future<Output = ()> + Send + 'static {
+
+        let close_state = Arc::new(Mutex::new(CloseState { closed: false, waker: None }));
+
+        // if the reaper thread already stopped, the closer is dropped right away
+        let _ignored = self.reaper.notifier.send(Message::Close(Instant::now() + grace, Closer(Arc::clone(&close_state))));
+
+        future::poll_fn(move |ctx| {
+            let mut guard = close_state.lock().unwrap();
+            if guard.closed {
+                return Poll::Ready(())
+            }
+            guard.waker = Some(ctx.waker().clone());
+            Poll::Pending
+        })
+
+    }
+
+    /// Looks up the address of a host, without sending a request.
+    ///
+    /// This uses the dns cache of the client, see [`Client::resolve`].
+    ///
+    /// # Example
+    ///
+    /// ```rust
+    /// let addr = client.lookup("example.com").await?;
+    /// ```
+    pub fn lookup(&self, host: &str) -> impl Future<Output = io::Result<IpAddr>> + Send + 'static {
 
         let request_state = Arc::new(Mutex::new(SimpleRequestState {
-            request: Some(request),
+            request: None,
+            lookup: Some(host.to_string()),
             resps: VecDeque::new(),
-            waker: None
+            waker: None,
+            trailers: None,
+            timings: None,
+            id: None,
+            finished: false,
+            cancelled: false,
+            paused: false,
+            resuming: false,
+            failed: None,
         }));
 
-        let reaper_clone = Arc::clone(&request_state);
-        self.sender.write_all(&(Arc::into_raw(reaper_clone) as u64).to_ne_bytes()).unwrap();
+        // dropping the reader cancels the lookup
+        let reader = self.queue(request_state, Method::Get);
 
         async move {
+            future::poll_fn(|ctx| {
+                let mut guard = reader.request_state.lock().unwrap();
+                guard.waker = Some(ctx.waker().clone());
+                match guard.resps.pop_front() {
+                    Some(ResponseState::Resolved(addr, _ttl)) => Poll::Ready(Ok(addr)),
+                    Some(other) => Poll::Ready(Err(other.into_io_error().unwrap())),
+                    None => guard.error().map_or(Poll::Pending, |err| Poll::Ready(Err(err))),
+                }
+            }).await
+        }
 
-            let head = future::poll_fn(|ctx| {
+    }
 
-                let mut guard = request_state.lock().unwrap();
+    /// Hands the request to the reaper thread, the returned reader receives the responses.
+    fn start(&self, mut request: RawRequest) -> BodyReader {
 
-                guard.waker = Some(ctx.waker().clone());
+        // the body is always read by the reaper thread, a redirect is just a head
+        request.pull_body = false;
+        request.report_redirects = None;
+        let method = request.method.clone();
 
-                if let Some(resp) = guard.resps.pop_front() {
-                    let result = match resp {
-                        ResponseState::Head(head) => Ok(head),
-                        error_or_data => Err(error_or_data.into_io_error().unwrap())
-                    };
-                    Poll::Ready(result)
-                } else {
-                    Poll::Pending
-                }
+        let request_state = Arc::new(Mutex::new(SimpleRequestState {
+            request: Some(request),
+            lookup: None,
+            resps: VecDeque::new(),
+            waker: None,
+            trailers: None,
+            timings: None,
+            id: None,
+            finished: false,
+            cancelled: false,
+            paused: false,
+            resuming: false,
+            failed: None,
+        }));
 
-            }).await?;
-        
-            let reader = BodyReader {
-                request_state,
-            };
+        self.queue(request_state, method)
 
-            Ok(SimpleResponse { head, body: reader })
-            
+    }
+
+    /// Hands the request state to the reaper thread.
+    fn queue(&self, request_state: Arc<Mutex<SimpleRequestState>>, method: Method) -> BodyReader {
+
+        // the reaper thread stopped because of an error
+        if let Err(err) = self.reaper.notifier.request(&request_state) {
+            request_state.lock().unwrap().fail(&err);
+        }
+
+        // the reader cancels the request when it is dropped, this also covers dropping the future
+        BodyReader {
+            request_state,
+            notifier: Arc::clone(&self.reaper.notifier),
+            method,
+            received: 0,
+            total: None,
+            buffer: Vec::new(),
+            offset: 0,
+            failed: false,
+        }
+
+    }
+
+    /// Waits for the head of the response.
+    async fn response(reader: io::Result<BodyReader>) -> io::Result<SimpleResponse<BodyReader>> {
+
+        let mut reader = reader?;
+
+        let head = future::poll_fn(|ctx| {
+
+            let mut guard = reader.request_state.lock().unwrap();
+
+            guard.waker = Some(ctx.waker().clone());
+
+            if let Some(resp) = guard.resps.pop_front() {
+                let result = match resp {
+                    ResponseState::Head(head) => Ok(head),
+                    error_or_data => Err(error_or_data.into_io_error().unwrap())
+                };
+                Poll::Ready(result)
+            } else if let Some(err) = guard.error() {
+                Poll::Ready(Err(err))
+            } else {
+                Poll::Pending
+            }
+
+        }).await?;
+
+        reader.total = head.body_length(&reader.method);
+        Ok(SimpleResponse { head, body: reader, timings: None })
+
+    }
+
+}
+
+/// What is sent to the reaper thread.
+enum Message {
+    /// The reaper thread sends the request, cancels it or continues uploading its body.
+    Request(Arc<Mutex<SimpleRequestState>>),
+    /// The client is drained until the deadline, see [`SimpleClient::close`].
+    Close(Instant, Closer),
+    Shutdown,
+}
+
+/// Whether the requests of a closed client finished, see [`SimpleClient::close`].
+struct CloseState {
+    closed: bool,
+    waker: Option<Waker>,
+}
+
+/// Held by the reaper thread until the requests of a closed client finished.
+///
+/// It is dropped once they did, or when the reaper thread stops for any other reason.
+struct Closer(Arc<Mutex<CloseState>>);
+
+impl Drop for Closer {
+    fn drop(&mut self) {
+        let mut guard = self.0.lock().unwrap();
+        guard.closed = true;
+        if let Some(waker) = guard.waker.take() {
+            waker.wake();
         }
+    }
+}
+
+/// Sends messages to the reaper thread and wakes it up.
+struct Notifier {
+    sender: mpsc::Sender<Message>,
+    waker: mio::Waker,
+}
+
+impl Notifier {
 
+    /// Sends the request state to the reaper thread, which then sends the request, cancels it or
+    /// continues uploading its body.
+    fn request(&self, request_state: &Arc<Mutex<SimpleRequestState>>) -> io::Result<()> {
+        self.send(Message::Request(Arc::clone(request_state)))
     }
 
-    fn shutdown(&mut self) {
-        // indicates to the reaper thread that it should shut itself down
-        self.sender.write_all(&[0; 8]).unwrap();
+    /// Fails if the reaper thread already shut down.
+    fn send(&self, message: Message) -> io::Result<()> {
+        self.sender.send(message).map_err(|_err| io::Error::new(io::ErrorKind::BrokenPipe, "the client was dropped"))?;
+        self.waker.wake()
     }
 
 }
 
+/// The part of an upload that was read but not sent yet.
+struct UploadPipe {
+    buffer: VecDeque<u8>,
+    closed: bool, // the whole body was read
+    failed: bool,
+    waker: Option<Waker>, // wakes the upload once there is space in the buffer again
+}
+
+/// Used by the reaper thread to read from an [`UploadPipe`].
+struct UploadReader {
+    pipe: Arc<Mutex<UploadPipe>>,
+}
+
+impl Read for UploadReader {
+    fn read(&mut self, buff: &mut [u8]) -> io::Result<usize> {
+        let mut guard = self.pipe.lock().unwrap();
+        if guard.failed {
+            return Err(io::Error::other("reading the request body failed"))
+        }
+        if guard.buffer.is_empty() && !guard.closed {
+            return Err(io::ErrorKind::WouldBlock.into())
+        }
+        let num = guard.buffer.read(buff)?;
+        if let Some(waker) = guard.waker.take() {
+            waker.wake();
+        }
+        Ok(num)
+    }
+}
+
+/// The result of a conditional request, see [`SimpleClient::fetch_conditional`].
+#[derive(Debug)]
+pub enum Conditional {
+    /// The resource changed or there was no cached response, this is the new response.
+    Fresh(SimpleResponse<Vec<u8>>),
+    /// The cached response is still fresh, this is the head of the `304 Not Modified` response.
+    NotModified(ResponseHead),
+}
+
 /// Allows streaming the body of a request.
 ///
 /// This does some internal buffering.
 /// For more information see [`SimpleClient::stream`].
 pub struct BodyReader {
     request_state: Arc<Mutex<SimpleRequestState>>,
+    notifier: Arc<Notifier>,
+    method: Method,
+    received: u64, // the bytes that were read from the reader
+    total: Option<u64>,
+    buffer: Vec<u8>, // taken from the queue but not consumed yet, see `AsyncBufRead`
+    offset: usize, // how much of `buffer` was consumed
+    failed: bool, // the stream yielded an error, so it ended
 }
 
-impl futures_io::AsyncRead for BodyReader {
+impl BodyReader {
 
-    fn poll_read(self: Pin<&mut Self>, ctx: &mut task::Context<'_>, buff: &mut [u8]) -> Poll<io::Result<usize>> {
+    /// Returns how much of the body was read from this reader, as [`Progress::Download`].
+    ///
+    /// The `total` is the `Content-Length`, it is `None` if the body is chunked or decompressed.
+    /// Buffered data that wasn't read yet is not counted.
+    pub fn progress(&self) -> Progress {
+        Progress::Download { received: self.received, total: self.total }
+    }
 
-        let mut guard = self.request_state.lock().unwrap();
+    /// The length of the body from the `Content-Length` header.
+    ///
+    /// Returns `None` if the body is chunked or decompressed, since the header doesn't tell its length then.
+    pub fn content_length(&self) -> Option<u64> {
+        self.total
+    }
 
-        if let Some(ref mut waker) = guard.waker {
-            waker.clone_from(ctx.waker()); // this clone from is optimized, see Waker::will_wake
+    /// How many bytes of the body were read from this reader.
+    pub fn bytes_read(&self) -> u64 {
+        self.received
+    }
+
+    /// Writes the rest of the body into the writer, the chunks are taken from the queue without copying them.
+    async fn write_into(&mut self, writer: &mut (impl AsyncWrite + Unpin)) -> io::Result<u64> {
+        let mut written = 0;
+        while let Some(chunk) = future::poll_fn(|ctx| self.poll_chunk(ctx)).await? {
+            writer.write_all(&chunk).await?;
+            written += chunk.len() as u64;
+        }
+        writer.flush().await?;
+        Ok(written)
+    }
+
+    /// A reader for responses that are already queued, there is no reaper thread that could receive more.
+    #[cfg(test)]
+    pub(crate) fn queued(resps: impl IntoIterator<Item = ResponseState>) -> io::Result<Self> {
+        let (sender, _receiver) = mpsc::channel();
+        let waker = mio::Waker::new(mio::Poll::new()?.registry(), SimpleClient::WAKER)?;
+        let request_state = SimpleRequestState {
+            request: None,
+            lookup: None,
+            resps: resps.into_iter().collect(),
+            waker: None,
+            trailers: None,
+            timings: None,
+            id: None,
+            finished: true,
+            cancelled: false,
+            paused: false,
+            resuming: false,
+            failed: None,
+        };
+        Ok(Self {
+            request_state: Arc::new(Mutex::new(request_state)),
+            notifier: Arc::new(Notifier { sender, waker }),
+            method: Method::Get,
+            received: 0,
+            total: None,
+            buffer: Vec::new(),
+            offset: 0,
+            failed: false,
+        })
+    }
+
+    /// The next piece of the body, `None` once the body is complete.
+    /// The rest of a partially consumed piece comes first.
+    fn poll_chunk(&mut self, ctx: &mut task::Context<'_>) -> Poll<io::Result<Option<Vec<u8>>>> {
+        let chunk = if self.offset < self.buffer.len() {
+            let mut rest = take(&mut self.buffer);
+            rest.drain(..self.offset);
+            self.offset = 0;
+            rest
         } else {
-            unreachable!()
+            match task::ready!(self.poll_queue(ctx))? {
+                Some(data) => data,
+                None => return Poll::Ready(Ok(None)),
+            }
+        };
+        self.received += chunk.len() as u64;
+        Poll::Ready(Ok(Some(chunk)))
+    }
+
+    /// Takes the next piece of the body from the queue, `None` once the body is complete.
+    fn poll_queue(&mut self, ctx: &mut task::Context<'_>) -> Poll<io::Result<Option<Vec<u8>>>> {
+
+        let mut guard = self.request_state.lock().unwrap();
+
+        match guard.waker {
+            Some(ref mut waker) => waker.clone_from(ctx.waker()), // this clone from is optimized, see Waker::will_wake
+            None => guard.waker = Some(ctx.waker().clone()),
         }
 
-        // read some data, only removing the response entry if one chunk of
-        // data was fully read
-        if let Some(resp) = guard.resps.front_mut() {
-            let result = match resp {
-                ResponseState::Head(..) => unreachable!(),
-                ResponseState::Data(data) => {
-                    let to_copy = data.len().min(buff.len());
-                    buff[..to_copy].copy_from_slice(&data[..to_copy]);
-                    data.truncate(data.len() - to_copy);
-                    if data.len() == 0 {
-                        guard.resps.pop_front();
-                    }
-                    Ok(to_copy)
+        let result = loop {
+            match guard.resps.front() {
+                None => return guard.error().map_or(Poll::Pending, |err| Poll::Ready(Err(err))),
+                Some(ResponseState::Head(..)) => unreachable!(),
+                // empty pieces are skipped, so they aren't mistaken for the end of the body
+                Some(ResponseState::Data(..)) => match guard.resps.pop_front() {
+                    Some(ResponseState::Data(data)) if data.is_empty() => continue,
+                    Some(ResponseState::Data(data)) => break Ok(Some(data)),
+                    _other => unreachable!(),
                 },
-                ResponseState::Done => Ok(0),
-                err => Err(err.into_io_error().unwrap())
-            };
-            drop(guard);
-            Poll::Ready(result)
-        } else {
-            drop(guard);
-            Poll::Pending
+                Some(ResponseState::Done) => break Ok(None),
+                Some(err) => break Err(err.into_io_error().unwrap()),
+            }
+        };
+
+        self.resume_reading(guard)?;
+        Poll::Ready(result)
+
+    }
+
+    /// Asks the reaper thread to continue reading the body, once the queue has room again.
+    fn resume_reading(&self, mut guard: MutexGuard<'_, SimpleRequestState>) -> io::Result<()> {
+        let resume = guard.paused && !guard.resuming && guard.buffered() < MAX_BODY_BUFFER;
+        guard.resuming |= resume;
+        drop(guard);
+        if resume {
+            self.notifier.request(&self.request_state)?;
         }
-        
+        Ok(())
+    }
+
+    /// Returns how long each phase of the request took, see [`Client::timings`].
+    ///
+    /// Returns `None` if the body wasn't read to the end yet.
+    pub fn timings(&self) -> Option<Timings> {
+        self.request_state.lock().unwrap().timings
+    }
+
+    /// Returns the trailers sent after a chunked body.
+    ///
+    /// They are only available once the whole body was read. Returns `None` if the
+    /// body wasn't read to the end yet or if the server didn't send any trailers.
+    pub fn take_trailers(&mut self) -> Option<Vec<OwnedHeader>> {
+        self.request_state.lock().unwrap().trailers.take()
     }
 
 }
 
-/// A simple response.
-///
-/// Use the alternate debug formatter `{:#?}` to print out verbose information
-/// including all headers and more.
-///
-/// The `body` may be a [`Vec<u8>`](std::vec::Vec), or a [`BodyReader`].
-#[derive(Clone)]
-pub struct SimpleResponse<B> {
-    pub head: ResponseHead,
-    pub body: B,
+impl Drop for BodyReader {
+    fn drop(&mut self) {
+        let mut guard = self.request_state.lock().unwrap();
+        if !guard.finished && !guard.cancelled {
+            guard.cancelled = true;
+            drop(guard);
+            // this fails if the client was already dropped, then there is nothing to cancel anyway
+            let _ignored = self.notifier.request(&self.request_state);
+        }
+    }
 }
 
-impl SimpleResponse<Vec<u8>> {
+impl futures_io::AsyncRead for BodyReader {
+    fn poll_read(self: Pin<&mut Self>, ctx: &mut task::Context<'_>, buff: &mut [u8]) -> Poll<io::Result<usize>> {
+        let this = self.get_mut();
+        let available = task::ready!(Pin::new(&mut *this).poll_fill_buf(ctx))?;
+        let num = available.len().min(buff.len());
+        buff[..num].copy_from_slice(&available[..num]);
+        Pin::new(this).consume(num);
+        Poll::Ready(Ok(num))
+    }
+}
+
+/// The buffer is the front of the next piece of the body, it is only empty once the whole body was read.
+impl AsyncBufRead for BodyReader {
+
+    fn poll_fill_buf(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> Poll<io::Result<&[u8]>> {
+        let this = self.get_mut();
+        if this.offset == this.buffer.len() {
+            this.buffer = task::ready!(this.poll_queue(ctx))?.unwrap_or_default();
+            this.offset = 0;
+        }
+        Poll::Ready(Ok(&this.buffer[this.offset..]))
+    }
 
-    /// Convert the request body into a `String`.
-    /// Note that the data is assumed to be valid utf8. Text encodings
-    /// are not handeled by this crate.
-    pub fn into_string(self) -> Result<String, string::FromUtf8Error> {
-        String::from_utf8(self.body)
+    fn consume(self: Pin<&mut Self>, amount: usize) {
+        let this = self.get_mut();
+        let amount = amount.min(this.buffer.len() - this.offset);
+        this.offset += amount;
+        this.received += amount as u64;
     }
 
 }
 
-impl<B> fmt::Debug for SimpleResponse<B> {
-    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        if f.alternate() {
-            write!(f, "SimpleResponse {{ head: {:?}, ... }}", self.head)
-        } else {
-            write!(f, "SimpleResponse {{ status: {}, {}, ... }}", self.head.status.code, self.head.status.reason)
+/// Yields the body in the pieces it was received in, without copying them.
+///
+/// The pieces don't correspond to the chunks of a chunked body, they contain whatever was received at once.
+/// Reading and streaming can be mixed, after a partial read the rest of that piece is yielded first.
+/// The stream ends once the whole body was received, or after yielding an error if the request failed.
+impl futures_lite::Stream for BodyReader {
+    type Item = io::Result<Vec<u8>>;
+    fn poll_next(self: Pin<&mut Self>, ctx: &mut task::Context<'_>) -> Poll<Option<Self::Item>> {
+        let this = self.get_mut();
+        if this.failed {
+            return Poll::Ready(None)
         }
+        let result = task::ready!(this.poll_chunk(ctx));
+        this.failed = result.is_err();
+        Poll::Ready(result.transpose())
     }
 }
-
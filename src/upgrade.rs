@@ -0,0 +1,137 @@
+//! Upgrading a connection to the WebSocket protocol, see [`Client::upgrade`](crate::Client::upgrade).
+
+use std::{fmt, io};
+use crate::{client::Connection, proxy::base64, util::random, OwnedHeader};
+
+/// Appended to the key before hashing it, see RFC 6455 section 1.3.
+const WEBSOCKET_GUID: &str = "258EAFA5-E914-47DA-95CA-C5AB0DC85B11";
+
+/// A connection that was handed over after the server switched protocols, see [`ResponseState::Upgraded`](crate::ResponseState::Upgraded).
+///
+/// It is still registered with the token of the request, for both `readable` and `writable` events.
+/// The token is in use until the connection is dropped, even though the client could hand it out again.
+/// Reading returns the bytes the server sent right after its response first.
+pub struct UpgradedConnection {
+    connection: Box<Connection>, // boxed, since tls connections are large
+    buffer: Vec<u8>, // what was read after the response head
+    offset: usize,
+}
+
+impl UpgradedConnection {
+
+    pub(crate) fn new(connection: Connection, buffer: Vec<u8>) -> Self {
+        Self { connection: Box::new(connection), buffer, offset: 0 }
+    }
+
+}
+
+impl io::Read for UpgradedConnection {
+    fn read(&mut self, buff: &mut [u8]) -> io::Result<usize> {
+        if self.offset < self.buffer.len() {
+            let num = buff.len().min(self.buffer.len() - self.offset);
+            buff[..num].copy_from_slice(&self.buffer[self.offset..self.offset + num]);
+            self.offset += num;
+            return Ok(num)
+        }
+        self.connection.read(buff)
+    }
+}
+
+impl io::Write for UpgradedConnection {
+    fn write(&mut self, buff: &[u8]) -> io::Result<usize> {
+        self.connection.write(buff)
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        self.connection.flush()
+    }
+}
+
+impl mio::event::Source for UpgradedConnection {
+    fn register(&mut self, registry: &mio::Registry, token: mio::Token, interests: mio::Interest) -> io::Result<()> {
+        self.connection.register(registry, token, interests)
+    }
+    fn reregister(&mut self, registry: &mio::Registry, token: mio::Token, interests: mio::Interest) -> io::Result<()> {
+        self.connection.reregister(registry, token, interests)
+    }
+    fn deregister(&mut self, registry: &mio::Registry) -> io::Result<()> {
+        self.connection.deregister(registry)
+    }
+}
+
+impl fmt::Debug for UpgradedConnection {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "UpgradedConnection {{ {} bytes buffered }}", self.buffer.len() - self.offset)
+    }
+}
+
+/// Every connection is unique, so it is only equal to itself.
+impl PartialEq for UpgradedConnection {
+    fn eq(&self, other: &Self) -> bool {
+        std::ptr::eq(self, other)
+    }
+}
+
+/// Returns a random `Sec-WebSocket-Key`.
+pub(crate) fn websocket_key() -> String {
+    let bytes = [random().to_ne_bytes(), random().to_ne_bytes()].concat();
+    base64(&bytes)
+}
+
+/// Returns the `Sec-WebSocket-Accept` the server has to answer with.
+pub(crate) fn websocket_accept(key: &str) -> String {
+    base64(&sha1(format!("{}{}", key, WEBSOCKET_GUID).as_bytes()))
+}
+
+/// Checks that the server switched to the WebSocket protocol using this key.
+pub(crate) fn accepted(headers: &[OwnedHeader], accept: &str) -> bool {
+    let header = |name: &str| headers.iter().find(|header| header.name.eq_ignore_ascii_case(name)).map(|header| header.value.trim());
+    header("Upgrade").is_some_and(|value| value.eq_ignore_ascii_case("websocket")) &&
+    header("Sec-WebSocket-Accept") == Some(accept)
+}
+
+/// Hashes the input using SHA-1, this is only used for the WebSocket handshake which isn't security related.
+fn sha1(input: &[u8]) -> [u8; 20] {
+
+    let mut state: [u32; 5] = [0x67452301, 0xEFCDAB89, 0x98BADCFE, 0x10325476, 0xC3D2E1F0];
+
+    // the input is padded with a single `1` bit, zeros and its length in bits
+    let mut message = input.to_vec();
+    message.push(0x80);
+    while message.len() % 64 != 56 { message.push(0) }
+    message.extend_from_slice(&(input.len() as u64 * 8).to_be_bytes());
+
+    for block in message.chunks(64) {
+
+        let mut words = [0u32; 80];
+        for (idx, word) in block.chunks(4).enumerate() {
+            words[idx] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for idx in 16..80 {
+            words[idx] = (words[idx - 3] ^ words[idx - 8] ^ words[idx - 14] ^ words[idx - 16]).rotate_left(1);
+        }
+
+        let [mut a, mut b, mut c, mut d, mut e] = state;
+        for (idx, word) in words.iter().enumerate() {
+            let (f, k) = match idx {
+                0..=19  => ((b & c) | (!b & d), 0x5A827999),
+                20..=39 => (b ^ c ^ d, 0x6ED9EBA1),
+                40..=59 => ((b & c) | (b & d) | (c & d), 0x8F1BBCDC),
+                _       => (b ^ c ^ d, 0xCA62C1D6),
+            };
+            let temp = a.rotate_left(5).wrapping_add(f).wrapping_add(e).wrapping_add(k).wrapping_add(*word);
+            (e, d, c, b, a) = (d, c, b.rotate_left(30), a, temp);
+        }
+
+        for (value, add) in state.iter_mut().zip([a, b, c, d, e]) {
+            *value = value.wrapping_add(add);
+        }
+
+    }
+
+    let mut output = [0; 20];
+    for (chunk, value) in output.chunks_mut(4).zip(state) {
+        chunk.copy_from_slice(&value.to_be_bytes());
+    }
+    output
+
+}
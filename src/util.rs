@@ -1,11 +1,10 @@
 
-use mio::{event::Source, Interest};
-use std::{net::{Ipv4Addr, SocketAddr, SocketAddrV4}, io, hash::{Hasher, Hash}, collections::hash_map::DefaultHasher};
+use mio::{event::Source, Interest, net::TcpStream};
+use std::{net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr}, io, hash::{Hasher, BuildHasher}, collections::hash_map::RandomState, time::{Duration, SystemTime, UNIX_EPOCH}};
 
-pub(crate) fn hash(input: impl Hash) -> u64 {
-    let mut hasher = DefaultHasher::new();
-    input.hash(&mut hasher);
-    hasher.finish()
+/// Returns a random number, this is not suitable for anything security related.
+pub(crate) fn random() -> u64 {
+    RandomState::new().build_hasher().finish()
 }
 
 pub fn wouldblock(err: &io::Error) -> bool {
@@ -16,8 +15,208 @@ pub(crate) fn notconnected(err: &io::Error) -> bool {
     err.kind() == io::ErrorKind::NotConnected
 }
 
-pub(crate) const fn make_socket_addr(ip_addr: Ipv4Addr, port: u16) -> SocketAddr {
-    SocketAddr::V4(SocketAddrV4::new(ip_addr, port))
+pub(crate) fn closed_by_peer(err: &io::Error) -> bool {
+    matches!(err.kind(), io::ErrorKind::BrokenPipe | io::ErrorKind::ConnectionReset | io::ErrorKind::ConnectionAborted | io::ErrorKind::UnexpectedEof)
+}
+
+pub(crate) const fn make_socket_addr(ip_addr: IpAddr, port: u16) -> SocketAddr {
+    SocketAddr::new(ip_addr, port)
+}
+
+/// Returns the address if the host is an ip literal.
+///
+/// Ipv6 literals have to be enclosed in brackets, like in the `Host` header.
+pub(crate) fn ip_literal(host: &str) -> Option<IpAddr> {
+    if let Some(inner) = host.strip_prefix('[').and_then(|rest| rest.strip_suffix(']')) {
+        inner.parse::<Ipv6Addr>().ok().map(IpAddr::V6)
+    } else {
+        host.parse::<Ipv4Addr>().ok().map(IpAddr::V4)
+    }
+}
+
+/// Returns `true` if the address can be reached from the internet, see [`RequestPolicy::block_private_ips`](crate::RequestPolicy::block_private_ips).
+pub(crate) fn is_public(addr: IpAddr) -> bool {
+    match addr {
+        IpAddr::V4(addr) => {
+            let [first, second, ..] = addr.octets();
+            // the "this network" block, the shared address space of carrier-grade nats (RFC 6598),
+            // the benchmarking block (RFC 2544) and the reserved 240.0.0.0/4, which includes the broadcast address
+            let reserved = first == 0 || (first == 100 && second & 0xc0 == 64) || (first == 198 && second & 0xfe == 18) || first >= 240;
+            !(reserved || addr.is_private() || addr.is_loopback() || addr.is_link_local() || addr.is_unspecified()
+                || addr.is_documentation() || addr.is_multicast())
+        },
+        IpAddr::V6(addr) => {
+            let segments = addr.segments();
+            let embedded = |high: u16, low: u16| IpAddr::V4(Ipv4Addr::from((u32::from(high) << 16) | u32::from(low)));
+            if let Some(mapped) = addr.to_ipv4_mapped() {
+                is_public(IpAddr::V4(mapped))
+            } else if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+                // NAT64 (RFC 6052) reaches the ipv4 address in the last 32 bits
+                is_public(embedded(segments[6], segments[7]))
+            } else if segments[0] == 0x2002 {
+                // 6to4 (RFC 3056) reaches the ipv4 address that follows the prefix
+                is_public(embedded(segments[1], segments[2]))
+            } else {
+                // the deprecated site-local addresses (fec0::/10) were private as well
+                let site_local = segments[0] & 0xffc0 == 0xfec0;
+                !(site_local || addr.is_loopback() || addr.is_unspecified() || addr.is_multicast() || addr.is_unique_local() || addr.is_unicast_link_local())
+            }
+        },
+    }
+}
+
+/// Parses an HTTP-date, in the preferred format (`Sun, 06 Nov 1994 08:49:37 GMT`) or one of the obsolete
+/// RFC 850 (`Sunday, 06-Nov-94 08:49:37 GMT`) and asctime (`Sun Nov  6 08:49:37 1994`) formats, see RFC 9110 section 5.6.7.
+///
+/// Dates before 1970 aren't supported.
+pub(crate) fn parse_http_date(value: &str) -> Option<SystemTime> {
+
+    const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+    let parts = value.split([' ', ',', '-']).filter(|part| !part.is_empty()).collect::<Vec<_>>();
+    let (day, month, year, time) = match parts[..] {
+        [_weekday, day, month, year, time, "GMT"] => (day, month, year, time),
+        [_weekday, month, day, time, year] => (day, month, year, time),
+        _ => return None,
+    };
+
+    let day: i64 = day.parse().ok().filter(|day| (1..=31).contains(day))?;
+    let month = MONTHS.iter().position(|name| *name == month)? as i64 + 1;
+    let year: i64 = match (year.len(), year.parse().ok()?) {
+        // a two digit year of the RFC 850 format
+        (2, year @ 0..=69) => year + 2000,
+        (2, year) => year + 1900,
+        (4, year) => year,
+        _other => return None,
+    };
+
+    let mut time = time.split(':').map(|part| part.parse::<i64>().ok());
+    let (Some(Some(hour @ 0..=23)), Some(Some(minute @ 0..=59)), Some(Some(second @ 0..=60)), None) = (time.next(), time.next(), time.next(), time.next()) else {
+        return None
+    };
+
+    // the days since 1970-01-01, with the year starting in march so the leap day is at the end
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let day_of_year = (153 * ((month + 9) % 12) + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146097 + day_of_era - 719468;
+
+    let seconds = days * 86400 + hour * 3600 + minute * 60 + second;
+    Some(UNIX_EPOCH + Duration::from_secs(seconds.try_into().ok()?))
+
+}
+
+/// Splits an authority like `host:port` or `[::1]:port` into the host and the port, if there is one.
+pub(crate) fn split_port(authority: &str) -> (&str, Option<&str>) {
+    // ipv6 addresses are enclosed in brackets
+    let colon = match authority.rfind(']') {
+        Some(bracket) => authority[bracket..].find(':').map(|colon| bracket + colon),
+        None => authority.rfind(':'),
+    };
+    match colon {
+        Some(colon) => (&authority[..colon], Some(&authority[colon + 1..])),
+        None => (authority, None),
+    }
+}
+
+/// Connects to `addr`, binding the socket to `local_address` first if there is one.
+pub(crate) fn connect_tcp(addr: SocketAddr, local_address: Option<IpAddr>) -> io::Result<TcpStream> {
+    match local_address {
+        Some(local_address) => connect_bound(addr, SocketAddr::new(local_address, 0)),
+        None => TcpStream::connect(addr),
+    }
+}
+
+#[cfg(unix)]
+fn connect_bound(addr: SocketAddr, local: SocketAddr) -> io::Result<TcpStream> {
+
+    use std::os::unix::io::{AsRawFd, FromRawFd};
+
+    fn check(result: libc::c_int) -> io::Result<libc::c_int> {
+        match result {
+            -1 => Err(io::Error::last_os_error()),
+            other => Ok(other),
+        }
+    }
+
+    fn raw_addr(addr: SocketAddr) -> (libc::sockaddr_storage, libc::socklen_t) {
+        let mut storage: libc::sockaddr_storage = unsafe { std::mem::zeroed() };
+        let len = match addr {
+            SocketAddr::V4(addr) => {
+                let raw = unsafe { &mut *(&mut storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_in) };
+                raw.sin_family = libc::AF_INET as libc::sa_family_t;
+                raw.sin_port = addr.port().to_be();
+                raw.sin_addr = libc::in_addr { s_addr: u32::from_ne_bytes(addr.ip().octets()) };
+                std::mem::size_of::<libc::sockaddr_in>()
+            },
+            SocketAddr::V6(addr) => {
+                let raw = unsafe { &mut *(&mut storage as *mut libc::sockaddr_storage as *mut libc::sockaddr_in6) };
+                raw.sin6_family = libc::AF_INET6 as libc::sa_family_t;
+                raw.sin6_port = addr.port().to_be();
+                raw.sin6_addr = libc::in6_addr { s6_addr: addr.ip().octets() };
+                raw.sin6_flowinfo = addr.flowinfo();
+                raw.sin6_scope_id = addr.scope_id();
+                std::mem::size_of::<libc::sockaddr_in6>()
+            },
+        };
+        (storage, len as libc::socklen_t)
+    }
+
+    let domain = if addr.is_ipv6() { libc::AF_INET6 } else { libc::AF_INET };
+    let fd = check(unsafe { libc::socket(domain, libc::SOCK_STREAM, 0) })?;
+    // the socket is owned right away so it is closed if anything fails
+    let socket = unsafe { std::net::TcpStream::from_raw_fd(fd) };
+    check(unsafe { libc::fcntl(socket.as_raw_fd(), libc::F_SETFD, libc::FD_CLOEXEC) })?;
+    socket.set_nonblocking(true)?;
+
+    let (local, local_len) = raw_addr(local);
+    check(unsafe { libc::bind(socket.as_raw_fd(), &local as *const libc::sockaddr_storage as *const libc::sockaddr, local_len) })?;
+
+    let (addr, addr_len) = raw_addr(addr);
+    match check(unsafe { libc::connect(socket.as_raw_fd(), &addr as *const libc::sockaddr_storage as *const libc::sockaddr, addr_len) }) {
+        Err(err) if err.raw_os_error() != Some(libc::EINPROGRESS) => return Err(err),
+        _connected_or_in_progress => (),
+    }
+
+    Ok(TcpStream::from_std(socket))
+
+}
+
+#[cfg(not(unix))]
+fn connect_bound(_addr: SocketAddr, _local: SocketAddr) -> io::Result<TcpStream> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, "binding to a local address is only supported on unix"))
+}
+
+/// Enables `SO_KEEPALIVE` and sets how long the connection has to be idle before keepalive probes are sent.
+///
+/// Setting the idle time isn't supported on every system, the system default is used there.
+#[cfg(unix)]
+pub(crate) fn set_keepalive(stream: &TcpStream, idle: std::time::Duration) -> io::Result<()> {
+
+    use std::os::unix::io::AsRawFd;
+
+    fn set_option(fd: libc::c_int, level: libc::c_int, name: libc::c_int, value: libc::c_int) -> io::Result<()> {
+        let size = std::mem::size_of::<libc::c_int>() as libc::socklen_t;
+        match unsafe { libc::setsockopt(fd, level, name, &value as *const libc::c_int as *const libc::c_void, size) } {
+            -1 => Err(io::Error::last_os_error()),
+            _ => Ok(()),
+        }
+    }
+
+    let fd = stream.as_raw_fd();
+    #[cfg_attr(not(any(target_os = "linux", target_os = "android", target_os = "freebsd", target_os = "netbsd", target_os = "dragonfly", target_os = "macos", target_os = "ios")), allow(unused_variables))]
+    let secs = idle.as_secs().clamp(1, libc::c_int::MAX as u64) as libc::c_int;
+
+    set_option(fd, libc::SOL_SOCKET, libc::SO_KEEPALIVE, 1)?;
+    #[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd", target_os = "netbsd", target_os = "dragonfly"))]
+    set_option(fd, libc::IPPROTO_TCP, libc::TCP_KEEPIDLE, secs)?;
+    #[cfg(any(target_os = "macos", target_os = "ios"))]
+    set_option(fd, libc::IPPROTO_TCP, libc::TCP_KEEPALIVE, secs)?;
+
+    Ok(())
+
 }
 
 pub(crate) fn register_all<S: Source>(io: &mio::Poll, source: &mut S, token: mio::Token) -> io::Result<()> {